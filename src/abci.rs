@@ -2,27 +2,35 @@
 //!
 //! This modules handles operations of the ABCI interface, which mostly interact with the on-chain
 //! store.
+use ics23::CommitmentProof;
+use prost_ics23::Message;
 use tendermint::abci::{Code, Log as AbciLog};
 use tendermint::block;
+use tendermint::merkle::proof::{Proof, ProofOp};
 use tendermint_rpc::endpoint::{
     abci_info::AbciInfo, abci_query::AbciQuery, abci_query::Request as AbciQueryRequest,
 };
 
 use crate::node::Node;
-use crate::store::Storage;
+use crate::store::{multistore, Storage, IBC_STORE};
+
+/// Proof operation type of the IBC store proofs, which follow the spec of tendermock's AVL tree
+/// (see `avl::get_proof_spec`) rather than the one of IAVL.
+const IBC_STORE_PROOF: &str = "ics23:tendermock";
+/// Proof operation type of the multistore proofs, which follow the ICS23 `tendermint_spec`.
+const MULTISTORE_PROOF: &str = "ics23:simple";
 
 /// Return information about the ABCI API.
 pub fn get_info<S: Storage>(node: &Node<S>) -> AbciInfo {
     let chain = node.get_chain();
+    let height = chain.get_height().revision_height;
     // TODO: inject valid informations
     AbciInfo {
         data: "data_placeholder".to_string(),
         version: "v0.17.0".to_string(),
         app_version: 1,
-        last_block_height: tendermint::block::Height::from(
-            chain.get_height().revision_height as u32,
-        ),
-        last_block_app_hash: vec![],
+        last_block_height: tendermint::block::Height::from(height as u32),
+        last_block_app_hash: chain.get_app_hash(height),
     }
 }
 
@@ -32,8 +40,32 @@ pub fn handle_query<S: Storage>(query: AbciQueryRequest, node: &Node<S>) -> Abci
         None => 0,
         Some(h) => h.value(),
     };
+    // Height 0 stands for the latest commited block
+    let height = if height == 0 {
+        node.get_chain().get_height().revision_height
+    } else {
+        height
+    };
     let store = node.get_store();
+    if store.is_pruned(height) {
+        return AbciQuery {
+            code: Code::Err(2),
+            log: AbciLog::from(format!("height {} has been pruned", height).as_str()),
+            info: "Height pruned".to_string(),
+            index: 0,
+            key: query.data,
+            value: vec![],
+            proof: None,
+            height: block::Height::from(height as u32),
+            codespace: "".to_string(),
+        };
+    }
     let item = store.get(height, &query.data);
+    let proof = if query.prove {
+        prove(store, height, &query.data)
+    } else {
+        None
+    };
     if let Some(item) = item {
         AbciQuery {
             code: Code::Ok,
@@ -42,7 +74,7 @@ pub fn handle_query<S: Storage>(query: AbciQueryRequest, node: &Node<S>) -> Abci
             index: 0,
             key: query.data,
             value: item.to_vec(),
-            proof: None,
+            proof,
             height: block::Height::from(height as u32),
             codespace: "".to_string(),
         }
@@ -54,9 +86,154 @@ pub fn handle_query<S: Storage>(query: AbciQueryRequest, node: &Node<S>) -> Abci
             index: 0,
             key: query.data,
             value: vec![],
-            proof: None,
+            proof,
             height: block::Height::from(height as u32),
             codespace: "".to_string(),
         }
     }
 }
+
+/// Build a proof of existence (or non-existence) of `key` at `height`.
+///
+/// The proof is made of two operations: the proof of `key` in the IBC store, and the proof of the
+/// IBC store root in the multistore, whose root is the application hash.
+fn prove<S: Storage>(store: &S, height: u64, key: &[u8]) -> Option<Proof> {
+    let store_proof = store.get_proof(height, key)?;
+    let multistore_proof = multistore(store, height).get_proof(IBC_STORE)?;
+    Some(Proof {
+        ops: vec![
+            proof_op(IBC_STORE_PROOF, key, store_proof),
+            proof_op(MULTISTORE_PROOF, IBC_STORE.as_bytes(), multistore_proof),
+        ],
+    })
+}
+
+/// Wrap an ICS23 commitment proof into a Tendermint proof operation.
+fn proof_op(field_type: &str, key: &[u8], proof: CommitmentProof) -> ProofOp {
+    let mut data = Vec::new();
+    proof
+        .encode(&mut data)
+        .expect("[Internal] Failed to encode ICS23 proof");
+    ProofOp {
+        field_type: field_type.to_owned(),
+        key: key.to_owned(),
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avl::get_proof_spec;
+    use crate::config::Config;
+    use crate::packets::PacketKeeper;
+    use ibc::ics24_host::identifier::{ChannelId, PortId};
+    use ics23::{tendermint_spec, verify_membership};
+    use std::str::FromStr;
+
+    #[test]
+    fn query_proof() {
+        let node = Node::new(&Config::default());
+        let key = b"clients/donald_duck/clientType".to_vec();
+        let value = b"07-tendermint".to_vec();
+        node.get_store().set(key.clone(), value.clone());
+        node.grow();
+
+        let query = AbciQueryRequest {
+            path: None,
+            data: key.clone(),
+            height: None,
+            prove: true,
+        };
+        let response = handle_query(query, &node);
+        assert_eq!(response.value, value);
+        let proof = response.proof.expect("The query should contain a proof");
+        assert_eq!(proof.ops.len(), 2);
+
+        // The header following the queried height commits to its state
+        let height = response.height.value();
+        let header = node
+            .get_chain()
+            .get_block(height + 1)
+            .unwrap()
+            .signed_header
+            .header;
+        let app_hash = header.app_hash.as_ref();
+        let store_root = node.get_store().root_hash(height).unwrap();
+        let store_proof = CommitmentProof::decode(&*proof.ops[0].data).unwrap();
+        let multistore_proof = CommitmentProof::decode(&*proof.ops[1].data).unwrap();
+        assert_eq!(proof.ops[0].field_type, IBC_STORE_PROOF);
+        assert_eq!(proof.ops[1].field_type, MULTISTORE_PROOF);
+        assert!(verify_membership(
+            &store_proof,
+            &get_proof_spec(),
+            &store_root,
+            &key,
+            &value
+        ));
+        assert!(verify_membership(
+            &multistore_proof,
+            &tendermint_spec(),
+            app_hash,
+            IBC_STORE.as_bytes(),
+            &store_root
+        ));
+    }
+
+    #[test]
+    fn query_packet_commitment() {
+        let mut shared_node = Node::new(&Config::default()).shared();
+        let port_channel_id = (
+            PortId::from_str("transfer").unwrap(),
+            ChannelId::from_str("channel-0").unwrap(),
+        );
+        let commitment = vec![1, 2, 3];
+        shared_node
+            .store_packet_commitment(&port_channel_id, 1, commitment.clone())
+            .unwrap();
+        shared_node.grow();
+
+        let key = b"commitments/ports/transfer/channels/channel-0/sequences/1".to_vec();
+        let query = AbciQueryRequest {
+            path: None,
+            data: key.clone(),
+            height: None,
+            prove: true,
+        };
+        let node = shared_node.read();
+        let response = handle_query(query, &node);
+        assert_eq!(response.code, Code::Ok);
+        assert_eq!(response.value, commitment);
+        let proof = response.proof.expect("The query should contain a proof");
+        let store_root = node.get_store().root_hash(response.height.value()).unwrap();
+        let store_proof = CommitmentProof::decode(&*proof.ops[0].data).unwrap();
+        assert!(verify_membership(
+            &store_proof,
+            &get_proof_spec(),
+            &store_root,
+            &key,
+            &commitment
+        ));
+    }
+
+    #[test]
+    fn query_pruned() {
+        let mut config = Config::default();
+        config.pruning = crate::config::Pruning::KeepRecent(1);
+        let node = Node::new(&config);
+        let key = b"foo".to_vec();
+        node.get_store().set(key.clone(), b"bar".to_vec());
+        node.grow();
+        node.grow();
+
+        let query = AbciQueryRequest {
+            path: None,
+            data: key,
+            height: Some(block::Height::from(2 as u32)),
+            prove: false,
+        };
+        let response = handle_query(query, &node);
+        assert_eq!(response.code, Code::Err(2));
+        assert!(response.value.is_empty());
+    }
+}
@@ -1,21 +1,30 @@
 //! # AVL Tree
 //!
-//! This module hosts a simple implementation of an AVL Merkle Tree that support the `get` and
-//! `insert` instructions (no delete yet, it's not needed as the on-chain store is supposed to be
-//! immutable).
+//! This module hosts a simple implementation of an AVL Merkle Tree that support the `get`,
+//! `insert` and `remove` instructions.
 //!
-//! Proof of existence are supported using [ICS23](https://github.com/confio/ics23), but proof of
-//! non-existence are not yet implemented.
+//! Proof of existence and non-existence are supported using
+//! [ICS23](https://github.com/confio/ics23). To be compatible with the ICS23 neighbour checks, the
+//! merkle hash of a node is computed as if the tree was a binary tree holding values in its leaves:
+//! the node's own hash is first combined with the hash of its left child, then with the hash of its
+//! right child (a missing child is simply skipped).
 //!
 //! Keys needs to implement `Ord` and `AsBytes` (see `as_bytes` module), while values are required
 //! to implement `Borrow<[u8]>`.
 //!
+//! The tree is persistent: nodes are reference counted and copied on write, which means that
+//! cloning a tree is cheap and that a clone shares all the unmodified sub-trees with the original.
+//! This is used by the store to keep one version of the tree per height.
+//!
 //! For more info, see [AVL Tree on wikipedia](https://en.wikipedia.org/wiki/AVL_tree),
 use ics23::commitment_proof::Proof;
-use ics23::{CommitmentProof, ExistenceProof, HashOp, InnerOp, LeafOp, LengthOp};
+use ics23::{
+    CommitmentProof, ExistenceProof, HashOp, InnerOp, LeafOp, LengthOp, NonExistenceProof,
+};
 use sha2::{Digest, Sha256};
 use std::borrow::Borrow;
 use std::cmp::{Ord, Ordering};
+use std::sync::Arc;
 use tendermint::hash::{Algorithm, Hash};
 
 mod as_bytes;
@@ -27,7 +36,7 @@ pub use proof::get_proof_spec;
 
 const HASH_ALGO: Algorithm = Algorithm::Sha256;
 
-type NodeRef<T, V> = Option<Box<AvlNode<T, V>>>;
+type NodeRef<T, V> = Option<Arc<AvlNode<T, V>>>;
 
 /// A node in the AVL Tree.
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -41,19 +50,36 @@ struct AvlNode<K: Ord, V> {
     right: NodeRef<K, V>,
 }
 
-/// An AVL Tree that supports `get` and `insert` operation and can be used to prove existence of a
-/// given key-value couple.
+/// An AVL Tree that supports `get`, `insert` and `remove` operations and can be used to prove
+/// existence of a given key-value couple.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct AvlTree<K: Ord + AsBytes, V> {
     root: NodeRef<K, V>,
 }
 
+/// Compute the hash of an inner node given the hashes of its two children.
+fn inner_hash(left: &[u8], right: &[u8]) -> Hash {
+    let mut sha = Sha256::new();
+    sha.update(left);
+    sha.update(right);
+    Hash::from_bytes(HASH_ALGO, sha.finalize().as_slice()).unwrap()
+}
+
+/// Build an ICS23 inner operation.
+fn inner_op(prefix: Vec<u8>, suffix: Vec<u8>) -> InnerOp {
+    InnerOp {
+        hash: HashOp::Sha256.into(),
+        prefix,
+        suffix,
+    }
+}
+
 /// Wrap a key + value couple into a `NodeRef`.
 fn as_node_ref<K: Ord + AsBytes, V>(key: K, value: V) -> NodeRef<K, V>
 where
     V: Borrow<[u8]>,
 {
-    Some(Box::new(AvlNode::new(key, value)))
+    Some(Arc::new(AvlNode::new(key, value)))
 }
 
 impl<K: Ord + AsBytes, V> AvlNode<K, V>
@@ -66,8 +92,8 @@ where
         sha.update(key.as_bytes());
         sha.update(value.borrow());
         let hash = sha.finalize();
-        let merkle_hash = Hash::from_bytes(HASH_ALGO, &Sha256::digest(&hash)).unwrap();
         let hash = Hash::from_bytes(HASH_ALGO, &hash).unwrap();
+        let merkle_hash = hash.clone();
         return AvlNode {
             key,
             value,
@@ -138,17 +164,21 @@ where
         }
     }
 
+    /// The hash of the node combined with the merkle hash of its left child, if any.
+    fn left_merkle_hash(&self) -> Hash {
+        match &self.left {
+            Some(left) => inner_hash(left.merkle_hash.as_bytes(), self.hash.as_bytes()),
+            None => self.hash.clone(),
+        }
+    }
+
     /// Update the node's merkle hash by looking at the hashes of its two children.
     fn update_hashes(&mut self) {
-        let mut sha = Sha256::new();
-        if let Some(left) = &self.left {
-            sha.update(&left.merkle_hash.as_bytes());
-        }
-        sha.update(&self.hash.as_bytes());
-        if let Some(right) = &self.right {
-            sha.update(right.merkle_hash.as_bytes())
-        }
-        self.merkle_hash = Hash::from_bytes(HASH_ALGO, sha.finalize().as_slice()).unwrap();
+        let left_merkle_hash = self.left_merkle_hash();
+        self.merkle_hash = match &self.right {
+            Some(right) => inner_hash(left_merkle_hash.as_bytes(), right.merkle_hash.as_bytes()),
+            None => left_merkle_hash,
+        };
     }
 
     /// Update node meda data, such as its height and merkle hash, by lookind at its two
@@ -169,7 +199,7 @@ where
     }
 }
 
-impl<K: Ord + AsBytes, V> AvlTree<K, V>
+impl<K: Ord + AsBytes + Clone, V: Clone> AvlTree<K, V>
 where
     V: Borrow<[u8]>,
 {
@@ -178,7 +208,6 @@ where
         AvlTree { root: None }
     }
 
-    #[allow(dead_code)]
     /// Return the hash of the merkle tree root, if it has at least one node.
     pub fn root_hash(&self) -> Option<&Hash> {
         Some(&self.root.as_ref()?.merkle_hash)
@@ -210,6 +239,7 @@ where
     /// Insert a value in the tree.
     fn insert_rec(node_ref: &mut NodeRef<K, V>, key: K, value: V) {
         if let Some(node) = node_ref {
+            let node = Arc::make_mut(node);
             match node.key.cmp(&key) {
                 Ordering::Greater => AvlTree::insert_rec(&mut node.left, key, value),
                 Ordering::Less => AvlTree::insert_rec(&mut node.right, key, value),
@@ -222,7 +252,74 @@ where
         }
     }
 
-    #[allow(dead_code)]
+    /// Remove a value from the AVL tree and return it, this operation runs in O(log(n)).
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        AvlTree::remove_rec(&mut self.root, key)
+    }
+
+    /// Remove a value from the tree.
+    fn remove_rec<Q: ?Sized>(node_ref: &mut NodeRef<K, V>, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let ordering = node_ref.as_ref()?.key.borrow().cmp(key);
+        let value = match ordering {
+            Ordering::Greater => {
+                let node = Arc::make_mut(node_ref.as_mut().unwrap());
+                AvlTree::remove_rec(&mut node.left, key)?
+            }
+            Ordering::Less => {
+                let node = Arc::make_mut(node_ref.as_mut().unwrap());
+                AvlTree::remove_rec(&mut node.right, key)?
+            }
+            Ordering::Equal => {
+                let node = node_ref.take().unwrap();
+                let mut node = Arc::try_unwrap(node).unwrap_or_else(|node| (*node).clone());
+                match (node.left.take(), node.right.take()) {
+                    (None, None) => (),
+                    (Some(left), None) => *node_ref = Some(left),
+                    (None, Some(right)) => *node_ref = Some(right),
+                    (Some(left), Some(right)) => {
+                        // Replace the node by its successor
+                        let mut right = Some(right);
+                        let mut successor = AvlTree::remove_min(&mut right);
+                        let successor_mut = Arc::make_mut(&mut successor);
+                        successor_mut.left = Some(left);
+                        successor_mut.right = right;
+                        *node_ref = Some(successor);
+                    }
+                }
+                node.value
+            }
+        };
+        if let Some(node) = node_ref {
+            Arc::make_mut(node).update();
+            AvlTree::balance_node(node_ref);
+        }
+        Some(value)
+    }
+
+    /// Remove the node with the smallest key from a (non empty) sub-tree and return it.
+    fn remove_min(node_ref: &mut NodeRef<K, V>) -> Arc<AvlNode<K, V>> {
+        let node = node_ref.as_mut().expect("[AVL]: Empty node in remove min");
+        let node = Arc::make_mut(node);
+        if node.left.is_some() {
+            let min = AvlTree::remove_min(&mut node.left);
+            node.update();
+            AvlTree::balance_node(node_ref);
+            min
+        } else {
+            let mut min = node_ref.take().unwrap();
+            *node_ref = Arc::make_mut(&mut min).right.take();
+            min
+        }
+    }
+
     /// Return an existence proof for the given element, if it exists.
     pub fn get_proof<Q: ?Sized>(&self, key: &Q) -> Option<CommitmentProof>
     where
@@ -235,6 +332,55 @@ where
         })
     }
 
+    /// Return a non-existence proof for the given key, if it does not exist.
+    ///
+    /// The proof contains the existence proofs of the left and right neighbours of the key, if
+    /// any. Returns None if the key exists or if the tree is empty.
+    pub fn get_non_existence_proof<Q: ?Sized>(&self, key: &Q) -> Option<CommitmentProof>
+    where
+        K: Borrow<Q>,
+        Q: Ord + AsBytes,
+    {
+        if self.root.is_none() || self.get(key).is_some() {
+            return None;
+        }
+        let (left, right) = self.get_neighbours(key);
+        let left = left.and_then(|k| self.get_proof_rec::<Q>(k.borrow(), &self.root));
+        let right = right.and_then(|k| self.get_proof_rec::<Q>(k.borrow(), &self.root));
+        Some(CommitmentProof {
+            proof: Some(Proof::Nonexist(NonExistenceProof {
+                key: key.as_bytes().to_owned(),
+                left,
+                right,
+            })),
+        })
+    }
+
+    /// Return the biggest key smaller than `key` and the smallest key bigger than `key`, if any.
+    fn get_neighbours<Q: ?Sized>(&self, key: &Q) -> (Option<&K>, Option<&K>)
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let mut left = None;
+        let mut right = None;
+        let mut node_ref = &self.root;
+        while let Some(ref node) = node_ref {
+            match node.key.borrow().cmp(key) {
+                Ordering::Greater => {
+                    right = Some(&node.key);
+                    node_ref = &node.left;
+                }
+                Ordering::Less => {
+                    left = Some(&node.key);
+                    node_ref = &node.right;
+                }
+                Ordering::Equal => break,
+            }
+        }
+        (left, right)
+    }
+
     /// Recursively build a proof of existence for the desired value.
     fn get_proof_rec<Q: ?Sized>(&self, key: &Q, node: &NodeRef<K, V>) -> Option<ExistenceProof>
     where
@@ -242,23 +388,19 @@ where
         Q: Ord,
     {
         if let Some(node) = node {
-            let empty_hash = [];
-            let (mut proof, prefix, suffix) = match node.key.borrow().cmp(key) {
+            let mut proof = match node.key.borrow().cmp(key) {
                 Ordering::Greater => {
-                    let proof = self.get_proof_rec(key, &node.left)?;
-                    let prefix = vec![];
-                    let mut suffix = Vec::with_capacity(64);
-                    suffix.extend(node.hash.as_bytes());
-                    suffix.extend(node.right_hash().unwrap_or(&empty_hash));
-                    (proof, prefix, suffix)
+                    let mut proof = self.get_proof_rec(key, &node.left)?;
+                    proof
+                        .path
+                        .push(inner_op(vec![], node.hash.as_bytes().to_vec()));
+                    proof
                 }
                 Ordering::Less => {
-                    let proof = self.get_proof_rec(key, &node.right)?;
-                    let suffix = vec![];
-                    let mut prefix = Vec::with_capacity(64);
-                    prefix.extend(node.left_hash().unwrap_or(&empty_hash));
-                    prefix.extend(node.hash.as_bytes());
-                    (proof, prefix, suffix)
+                    let mut proof = self.get_proof_rec(key, &node.right)?;
+                    let prefix = node.left_merkle_hash().as_bytes().to_vec();
+                    proof.path.push(inner_op(prefix, vec![]));
+                    return Some(proof);
                 }
                 Ordering::Equal => {
                     let leaf = Some(LeafOp {
@@ -268,23 +410,21 @@ where
                         length: LengthOp::NoPrefix.into(),
                         prefix: proof::LEAF_PREFIX.to_vec(),
                     });
-                    let proof = ExistenceProof {
+                    let mut proof = ExistenceProof {
                         key: node.key.as_bytes().to_owned(),
                         value: node.value.borrow().to_owned(),
                         leaf,
                         path: vec![],
                     };
-                    let prefix = node.left_hash().unwrap_or(&empty_hash).to_vec();
-                    let suffix = node.right_hash().unwrap_or(&empty_hash).to_vec();
-                    (proof, prefix, suffix)
+                    if let Some(left_hash) = node.left_hash() {
+                        proof.path.push(inner_op(left_hash.to_vec(), vec![]));
+                    }
+                    proof
                 }
             };
-            let inner = InnerOp {
-                hash: HashOp::Sha256.into(),
-                prefix,
-                suffix,
-            };
-            proof.path.push(inner);
+            if let Some(right_hash) = node.right_hash() {
+                proof.path.push(inner_op(vec![], right_hash.to_vec()));
+            }
             Some(proof)
         } else {
             None
@@ -297,12 +437,16 @@ where
             .as_mut()
             .expect("[AVL]: Empty node in node balance");
         let balance_factor = node.balance_factor();
+        if balance_factor < 2 && balance_factor > -2 {
+            return;
+        }
+        let node = Arc::make_mut(node);
         if balance_factor >= 2 {
             let left = node
                 .left
                 .as_mut()
                 .expect("[AVL]: Unexpected empty left node");
-            if left.balance_factor() >= 1 {
+            if left.balance_factor() >= 0 {
                 AvlTree::rotate_right(node_ref);
             } else {
                 AvlTree::rotate_left(&mut node.left);
@@ -313,7 +457,7 @@ where
                 .right
                 .as_mut()
                 .expect("[AVL]: Unexpected empty right node");
-            if right.balance_factor() <= -1 {
+            if right.balance_factor() <= 0 {
                 AvlTree::rotate_left(node_ref);
             } else {
                 AvlTree::rotate_right(&mut node.right);
@@ -325,25 +469,33 @@ where
     /// Performs a right rotation.
     fn rotate_right(root: &mut NodeRef<K, V>) {
         let mut node = root.take().expect("[AVL]: Empty root in right rotation");
-        let mut left = node.left.take().expect("[AVL]: Unexpected right rotation");
-        let mut left_right = left.right.take();
-        std::mem::swap(&mut node.left, &mut left_right);
-        node.update();
-        std::mem::swap(&mut left.right, &mut Some(node));
-        left.update();
-        std::mem::swap(root, &mut Some(left));
+        let node_mut = Arc::make_mut(&mut node);
+        let mut left = node_mut
+            .left
+            .take()
+            .expect("[AVL]: Unexpected right rotation");
+        let left_mut = Arc::make_mut(&mut left);
+        node_mut.left = left_mut.right.take();
+        node_mut.update();
+        left_mut.right = Some(node);
+        left_mut.update();
+        *root = Some(left);
     }
 
     /// Perform a left rotation.
     fn rotate_left(root: &mut NodeRef<K, V>) {
         let mut node = root.take().expect("[AVL]: Empty root in left rotation");
-        let mut right = node.right.take().expect("[AVL]: Unexpected left rotation");
-        let mut right_left = right.left.take();
-        std::mem::swap(&mut node.right, &mut right_left);
-        node.update();
-        std::mem::swap(&mut right.left, &mut Some(node));
-        right.update();
-        std::mem::swap(root, &mut Some(right))
+        let node_mut = Arc::make_mut(&mut node);
+        let mut right = node_mut
+            .right
+            .take()
+            .expect("[AVL]: Unexpected left rotation");
+        let right_mut = Arc::make_mut(&mut right);
+        node_mut.right = right_mut.left.take();
+        node_mut.update();
+        right_mut.left = Some(node);
+        right_mut.update();
+        *root = Some(right);
     }
 
     /// Return a list of the keys present in the tree.
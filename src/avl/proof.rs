@@ -18,11 +18,11 @@ pub fn get_proof_spec() -> ProofSpec {
             prefix: LEAF_PREFIX.to_vec(),
         }),
         inner_spec: Some(InnerSpec {
-            child_order: vec![0, 1, 2],
+            child_order: vec![0, 1],
             child_size: 32,
             min_prefix_length: 0,
-            max_prefix_length: 64,
-            empty_child: vec![0, 32],
+            max_prefix_length: 0,
+            empty_child: vec![],
             hash: HashOp::Sha256.into(),
         }),
         max_depth: 0,
@@ -3,7 +3,8 @@
 #[cfg(test)]
 mod tests {
     use crate::avl::*;
-    use ics23::verify_membership;
+    use ics23::{verify_membership, verify_non_membership};
+    use std::sync::Arc;
 
     #[test]
     fn insert() {
@@ -33,6 +34,68 @@ mod tests {
         assert_eq!(tree.get(&[4]), None);
     }
 
+    #[test]
+    fn remove() {
+        let mut tree = AvlTree::new();
+        let keys: Vec<[u8; 1]> = (0..32).map(|k| [k]).collect();
+        for key in &keys {
+            tree.insert(*key, *key);
+        }
+        // Remove leaves, nodes with a single child and nodes with two children
+        for key in keys.iter().step_by(3) {
+            assert_eq!(tree.remove(key), Some(*key));
+            assert!(check_integrity(&tree.root));
+        }
+        assert_eq!(tree.remove(&[0]), None);
+        for key in &keys {
+            if key[0] % 3 == 0 {
+                assert_eq!(tree.get(key), None);
+            } else {
+                assert_eq!(tree.get(key), Some(key));
+            }
+        }
+        // The remaining keys must match the ones of a tree built without the removed keys
+        let mut target = AvlTree::new();
+        for key in keys.iter().filter(|k| k[0] % 3 != 0) {
+            target.insert(*key, *key);
+        }
+        assert_eq!(tree.get_keys(), target.get_keys());
+        let root = tree.root_hash().unwrap().as_bytes().to_vec();
+        let proof = tree.get_proof(&[4]).unwrap();
+        assert!(verify_membership(
+            &proof,
+            &get_proof_spec(),
+            &root,
+            &[4],
+            &[4]
+        ));
+        for key in &keys {
+            tree.remove(key);
+        }
+        assert_eq!(tree, AvlTree::new());
+    }
+
+    #[test]
+    fn structural_sharing() {
+        let mut tree = AvlTree::new();
+        for i in 0..64u8 {
+            tree.insert([i], [i]);
+        }
+        let snapshot = tree.clone();
+        tree.insert([64], [64]);
+        tree.remove(&[0]);
+        // The snapshot is left untouched
+        assert_eq!(snapshot.get(&[0]), Some(&[0]));
+        assert_eq!(snapshot.get(&[64]), None);
+        assert_eq!(snapshot.get_keys().len(), 64);
+        assert!(check_integrity(&snapshot.root));
+        // Sub-trees that were not modified are shared
+        let root = tree.root.as_ref().unwrap();
+        let snapshot_root = snapshot.root.as_ref().unwrap();
+        assert!(!Arc::ptr_eq(root, snapshot_root));
+        assert!(shares_node(&tree.root, &snapshot.root));
+    }
+
     #[test]
     fn rotate_right() {
         let mut before = AvlTree {
@@ -92,7 +155,7 @@ mod tests {
             Proof::Exist(proof) => proof,
             _ => panic!("Should return an existence proof"),
         };
-        assert_eq!(proof.path.len(), 2);
+        assert_eq!(proof.path.len(), 1);
         // Apply leaf transformations
         let leaf = proof
             .leaf
@@ -103,19 +166,12 @@ mod tests {
         sha.update("B".as_bytes());
         sha.update([1]);
         let child_hash = sha.finalize();
-        // Apply first inner node transformations
-        let inner_b = &proof.path[0];
-        let mut sha = Sha256::new();
-        sha.update(&inner_b.prefix);
-        sha.update(child_hash);
-        sha.update(&inner_b.suffix);
-        let inner_hash_b = sha.finalize();
-        assert_eq!(inner_hash_b.as_slice(), node_b.merkle_hash.as_bytes());
-        // Apply second inner node transformations
-        let inner_a = &proof.path[1];
+        assert_eq!(child_hash.as_slice(), node_b.merkle_hash.as_bytes());
+        // Apply inner node transformations
+        let inner_a = &proof.path[0];
         let mut sha = Sha256::new();
         sha.update(&inner_a.prefix);
-        sha.update(&inner_hash_b);
+        sha.update(child_hash);
         sha.update(&inner_a.suffix);
         let inner_hash_a = sha.finalize();
         assert_eq!(inner_hash_a.as_slice(), node_a.merkle_hash.as_bytes());
@@ -188,6 +244,32 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn non_existence_proof() {
+        let mut tree = AvlTree::new();
+        for key in &["B", "D", "F", "H", "J", "L", "N"] {
+            tree.insert(*key, [0]);
+        }
+        let root = tree
+            .root_hash()
+            .expect("Unable to retrieve root hash")
+            .as_bytes()
+            .to_vec();
+        let spec = get_proof_spec();
+        // Left-most, in between two keys and right-most
+        for key in &["A", "C", "G", "K", "M", "O"] {
+            let proof = tree
+                .get_non_existence_proof(*key)
+                .expect("Unable to retrieve a non-existence proof");
+            assert!(verify_non_membership(&proof, &spec, &root, key.as_bytes()));
+        }
+        // No non-existence proof for existing keys
+        assert!(tree.get_non_existence_proof("F").is_none());
+        // A proof for "C" does not prove the non-existence of "E"
+        let proof = tree.get_non_existence_proof("C").unwrap();
+        assert!(!verify_non_membership(&proof, &spec, &root, "E".as_bytes()));
+    }
+
     /// Check that nodes are ordered, heights are correct and that balance factors are in {-1, 0, 1}.
     fn check_integrity<T: Ord, V>(node_ref: &NodeRef<T, V>) -> bool {
         if let Some(node) = node_ref {
@@ -229,17 +311,30 @@ mod tests {
         }
     }
 
+    /// Returns true if the two trees share at least one sub-tree.
+    fn shares_node<T: Ord, V>(a: &NodeRef<T, V>, b: &NodeRef<T, V>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                Arc::ptr_eq(a, b)
+                    || shares_node(&a.left, &b.left)
+                    || shares_node(&a.right, &b.right)
+            }
+            _ => false,
+        }
+    }
+
     /// An helper function to build simple AvlNodes.
-    fn build_node<T: Ord + AsBytes>(
+    fn build_node<T: Ord + AsBytes + Clone>(
         key: T,
         value: [u8; 1],
         left: NodeRef<T, [u8; 1]>,
         right: NodeRef<T, [u8; 1]>,
     ) -> NodeRef<T, [u8; 1]> {
         let mut node = as_node_ref(key, value).unwrap();
-        node.left = left;
-        node.right = right;
-        node.update();
+        let node_mut = Arc::make_mut(&mut node);
+        node_mut.left = left;
+        node_mut.right = right;
+        node_mut.update();
         Some(node)
     }
 }
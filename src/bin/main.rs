@@ -26,6 +26,10 @@ pub struct Args {
     /// Seconds between two blocks, 0 for no growth
     #[clap(short, long, default_value = "20")]
     pub block: u64,
+
+    /// Path to a file persisting the chain state, the chain is restored from it on startup
+    #[clap(short, long)]
+    pub store: Option<String>,
 }
 
 fn main() {
@@ -39,8 +43,13 @@ fn main() {
         .add_interface(jrpc_addr, grpc_addr)
         .growth_rate(args.block);
     if let Some(config_path) = args.config {
-        tendermock.load_config(config_path);
+        if let Err(e) = tendermock.load_config(config_path) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(store_path) = args.store {
+        tendermock.persist(store_path);
     }
     tendermock.start();
 }
-
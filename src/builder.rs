@@ -4,7 +4,8 @@
 //!
 //! It is the public API for interacting with Tendermock.
 
-use crate::config::Config;
+use crate::chain;
+use crate::config::{Config, ConfigError, Storage};
 use crate::grpc;
 use crate::init;
 use crate::jrpc;
@@ -12,14 +13,17 @@ use crate::logger::Log;
 use crate::node;
 use crate::store;
 
-use futures::future::try_join_all;
-use futures::try_join;
+use futures::future::{join_all, FutureExt};
 use tokio;
+use tokio::sync::watch;
 
+use std::future::Future;
 use std::net::SocketAddr;
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 /// Tendermock builder object.
+#[derive(Clone)]
 pub struct Tendermock {
     /// Interval between new blocks, in seconds.
     growth_interval: u64,
@@ -32,6 +36,67 @@ pub struct Tendermock {
 
     /// Activate/deactivate verbose logging.
     verbose: bool,
+
+    /// A handle to control the node once started.
+    control: Control,
+}
+
+/// A handle to control the block production of a Tendermock node, see `Tendermock::control`.
+///
+/// The methods have no effect and return None until the node is started.
+#[derive(Clone)]
+pub struct Control {
+    node: Arc<RwLock<Option<node::SharedNode<store::DynStorage>>>>,
+}
+
+impl Control {
+    fn new() -> Self {
+        Control {
+            node: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Add a block to the chain, return the new height.
+    pub fn grow(&self) -> Option<u64> {
+        self.grow_n(1)
+    }
+
+    /// Add `n` blocks to the chain, return the new height.
+    pub fn grow_n(&self, n: u64) -> Option<u64> {
+        let node = self.node.read().unwrap();
+        let node = node.as_ref()?;
+        node.grow_n(n);
+        let height = node.read().get_chain().get_height().revision_height;
+        Some(height)
+    }
+
+    /// Set the time of the next block, in seconds since the unix epoch, and switch to a manual
+    /// clock. Return the time of the next block, which can not be before the latest block.
+    pub fn set_time(&self, time: u64) -> Option<u64> {
+        let node = self.node.read().unwrap();
+        let time = node.as_ref()?.read().get_chain().set_time(time);
+        Some(time)
+    }
+
+    /// Move the time of the next block forward by `seconds` and switch to a manual clock. Return
+    /// the time of the next block.
+    pub fn advance_time(&self, seconds: u64) -> Option<u64> {
+        let node = self.node.read().unwrap();
+        let time = node.as_ref()?.read().get_chain().advance_time(seconds);
+        Some(time)
+    }
+
+    /// Pause the automatic block production.
+    pub fn pause(&self) -> Option<()> {
+        self.node.read().unwrap().as_ref()?.pause();
+        Some(())
+    }
+
+    /// Resume the automatic block production.
+    pub fn resume(&self) -> Option<()> {
+        self.node.read().unwrap().as_ref()?.resume();
+        Some(())
+    }
 }
 
 impl Tendermock {
@@ -46,6 +111,7 @@ impl Tendermock {
             interfaces: vec![],
             config: Config::default(),
             verbose: false,
+            control: Control::new(),
         }
     }
 
@@ -55,15 +121,57 @@ impl Tendermock {
         self
     }
 
-    /// Add a new interface (one JsonRPC and one gRPC address).
+    /// Add a new interface (one JsonRPC and one gRPC address), a port of 0 binds to an ephemeral
+    /// port.
     pub fn add_interface(&mut self, jrpc: SocketAddr, grpc: SocketAddr) -> &mut Self {
         self.interfaces.push((jrpc, grpc));
         self
     }
 
-    /// Load the configuration of the genesis block.
-    pub fn load_config<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
-        self.config = Config::load(path);
+    /// Add a new interface bound to ephemeral ports on localhost, the actual ports are available
+    /// through the handle returned by `spawn` or `run`.
+    pub fn add_ephemeral_interface(&mut self) -> &mut Self {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        self.add_interface(addr, addr)
+    }
+
+    /// Load the configuration of the genesis block, return an error if the file can not be read
+    /// or if the configuration is invalid.
+    pub fn load_config<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, ConfigError> {
+        self.config = Config::load(path)?;
+        Ok(self)
+    }
+
+    /// Set the chain id, default to `tendermock`.
+    pub fn chain_id(&mut self, chain_id: &str) -> &mut Self {
+        self.config.chain_id = chain_id.to_owned();
+        self
+    }
+
+    /// Bind a port at genesis, such as `transfer`.
+    pub fn bind_port(&mut self, port: &str) -> &mut Self {
+        if !self.config.ports.iter().any(|p| p == port) {
+            self.config.ports.push(port.to_owned());
+        }
+        self
+    }
+
+    /// Persist the state of the chain to a file, the chain is restored from that file if it
+    /// already exists.
+    pub fn persist<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.config.storage = Storage::File(path.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Set the clock of the chain, default to the wall-clock time.
+    pub fn clock(&mut self, clock: chain::Clock) -> &mut Self {
+        self.config.clock = clock.into();
+        self
+    }
+
+    /// Set the pruning policy of the historical states, default to keeping all heights.
+    pub fn pruning(&mut self, pruning: store::Pruning) -> &mut Self {
+        self.config.pruning = pruning.into();
         self
     }
 
@@ -73,76 +181,509 @@ impl Tendermock {
         self
     }
 
+    /// Return a handle to control the node once started, as `start` is blocking the handle must
+    /// be retrieved beforehand.
+    pub fn control(&self) -> Control {
+        self.control.clone()
+    }
+
     /// Start the Tendermock node.
     ///
-    /// This call is blocking, for running multiple nodes simultaneously threading can be used (a
-    /// scheduler will run on each thread).
+    /// This call is blocking, see `spawn` and `run` for non-blocking alternatives.
     pub fn start(&self) {
-        // Initialize node
-        let node = node::Node::new(&self.config);
-        let mut node = node.shared();
-        init::init(&mut node, &self.config);
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async { self.run().await.join().await });
+    }
+
+    /// Start the Tendermock node on a dedicated thread, return once the servers are bound.
+    pub fn spawn(&self) -> TendermockHandle {
+        let builder = self.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(async move {
+                    let handle = builder.run().await;
+                    let TendermockHandle {
+                        interfaces,
+                        node,
+                        shutdown,
+                        server,
+                    } = handle;
+                    sender.send((interfaces, node, shutdown)).unwrap();
+                    if let Server::Task(task) = server {
+                        let _ = task.await;
+                    }
+                });
+        });
+        let (interfaces, node, shutdown) = receiver.recv().expect("Failed to start Tendermock");
+        TendermockHandle {
+            interfaces,
+            node,
+            shutdown,
+            server: Server::Thread(thread),
+        }
+    }
 
-        // Build servers
-        let mut jrpc_servers = Vec::new();
-        let mut grpc_servers = Vec::new();
+    /// Start the Tendermock node on the current Tokio runtime, return once the servers are bound.
+    pub async fn run(&self) -> TendermockHandle {
+        let node = self.build_node();
+        self.serve(node)
+    }
+
+    /// Commit the pending state of `node`, that is its genesis state or the clients created by a
+    /// `Network`, then bind and start its servers.
+    fn serve(&self, node: node::SharedNode<store::DynStorage>) -> TendermockHandle {
+        node.grow();
+        if self.verbose {
+            display_last_block(&node);
+        }
+        let (shutdown, signal) = watch::channel(false);
+
+        // Bind servers
+        let mut servers = Vec::new();
+        let mut interfaces = Vec::new();
         if self.interfaces.is_empty() {
             log!(Log::Chain, "Warning: no interface configured");
         }
         for (jrpc_addr, grpc_addr) in &self.interfaces {
+            let (jrpc_addr, jrpc_server) = jrpc::serve(
+                node.clone(),
+                self.verbose,
+                *jrpc_addr,
+                shutdown_signal(signal.clone()),
+            )
+            .unwrap_or_else(|e| panic!("Could not bind JsonRPC server: {}: {}", jrpc_addr, e));
+            let (grpc_addr, grpc_server) = grpc::serve(
+                node.clone(),
+                self.verbose,
+                *grpc_addr,
+                shutdown_signal(signal.clone()),
+            )
+            .unwrap_or_else(|e| panic!("Could not bind gRPC server: {}: {}", grpc_addr, e));
             if self.verbose {
                 log!(Log::GRPC, "Listening on: {}", &grpc_addr);
                 log!(Log::JRPC, "Listening on: {}", &jrpc_addr);
             }
-            let jrpc_server = jrpc::serve(node.clone(), self.verbose, jrpc_addr.clone());
-            let grpc_server = grpc::serve(node.clone(), self.verbose, grpc_addr.clone());
-            jrpc_servers.push(jrpc_server);
-            grpc_servers.push(grpc_server);
+            if interfaces.is_empty() {
+                node.write().set_listen_addr(jrpc_addr);
+            }
+            servers.push(jrpc_server.boxed());
+            servers.push(grpc_server.boxed());
+            interfaces.push((jrpc_addr, grpc_addr));
         }
+        servers.push(
+            schedule_growth(
+                node.clone(),
+                self.growth_interval,
+                self.verbose,
+                shutdown_signal(signal),
+            )
+            .boxed(),
+        );
 
         // Start servers
+        let task = tokio::spawn(join_all(servers).map(|_| ()));
+        TendermockHandle {
+            interfaces,
+            node,
+            shutdown: Shutdown(shutdown),
+            server: Server::Task(task),
+        }
+    }
+
+    /// Create the node and initialize its store.
+    ///
+    /// The genesis state is not yet commited, see `serve`.
+    ///
+    /// Panics if the configuration is invalid, which may happen if it has been modified through
+    /// the builder (for instance with `bind_port`).
+    fn build_node(&self) -> node::SharedNode<store::DynStorage> {
+        if let Err(e) = self.config.validate() {
+            panic!("{}", e);
+        }
+        let store: store::DynStorage = match &self.config.storage {
+            Storage::InMemory => Box::new(store::InMemoryStore::with_pruning(
+                self.config.pruning.into(),
+            )),
+            Storage::File(path) => Box::new(
+                store::FileStore::open(path, self.config.pruning.into())
+                    .unwrap_or_else(|e| panic!("Could not open store: {}: {}", path, e)),
+            ),
+        };
+        let node = node::Node::with_store(&self.config, store);
+        let mut node = node.shared();
+        // A restored chain has already been initialized
+        if node.read().get_chain().get_height().revision_height == 1 {
+            init::init(&mut node, &self.config);
+        }
+        *self.control.node.write().unwrap() = Some(node.clone());
+        node
+    }
+}
+
+/// A running Tendermock node, returned by `Tendermock::spawn` and `Tendermock::run`.
+///
+/// Dropping the handle shuts the node down in the background, use `shutdown` to wait for the
+/// servers to stop.
+pub struct TendermockHandle {
+    /// The bound interfaces, the first address for JsonRPC and the second for gRPC.
+    interfaces: Vec<(SocketAddr, SocketAddr)>,
+    node: node::SharedNode<store::DynStorage>,
+    shutdown: Shutdown,
+    server: Server,
+}
+
+/// Requests the servers of a node to stop, either explicitly or once dropped.
+struct Shutdown(watch::Sender<bool>);
+
+impl Shutdown {
+    fn send(&self) {
+        let _ = self.0.send(true);
+    }
+}
+
+impl Drop for Shutdown {
+    fn drop(&mut self) {
+        self.send();
+    }
+}
+
+/// Where the servers of a running node are executed.
+enum Server {
+    /// A task on a Tokio runtime owned by the caller.
+    Task(tokio::task::JoinHandle<()>),
+    /// A dedicated thread, with its own runtime.
+    Thread(std::thread::JoinHandle<()>),
+    /// A runtime shared with other nodes, see `Network::spawn`.
+    Shared,
+}
+
+impl TendermockHandle {
+    /// The bound interfaces, the first address for JsonRPC and the second for gRPC.
+    pub fn interfaces(&self) -> &[(SocketAddr, SocketAddr)] {
+        &self.interfaces
+    }
+
+    /// The address of the first JsonRPC server.
+    pub fn jrpc_addr(&self) -> SocketAddr {
+        self.interfaces.first().expect("No interface configured").0
+    }
+
+    /// The address of the first gRPC server.
+    pub fn grpc_addr(&self) -> SocketAddr {
+        self.interfaces.first().expect("No interface configured").1
+    }
+
+    /// The running node.
+    pub fn node(&self) -> &node::SharedNode<store::DynStorage> {
+        &self.node
+    }
+
+    /// Gracefully shut the node down.
+    ///
+    /// For a node started with `spawn` this blocks until the servers are stopped, otherwise the
+    /// servers stop in the background.
+    pub fn shutdown(self) {
+        self.shutdown.send();
+        if let Server::Thread(thread) = self.server {
+            thread.join().expect("Tendermock thread panicked");
+        }
+    }
+
+    /// Wait until the node stops.
+    async fn join(self) {
+        let TendermockHandle {
+            shutdown, server, ..
+        } = self;
+        match server {
+            Server::Task(task) => {
+                let _ = task.await;
+            }
+            Server::Thread(thread) => {
+                let _ = thread.join();
+            }
+            Server::Shared => {}
+        }
+        drop(shutdown);
+    }
+}
+
+/// A multi-chain builder, which runs several Tendermock nodes in the same runtime.
+///
+/// At genesis each chain holds a client of every other chain, whose consensus state is built from
+/// the counterparty's first block after genesis. A relayer can thus start with the connection
+/// handshakes.
+///
+/// ```no_run
+/// use tendermock::{Network, Tendermock};
+///
+/// let handle = Network::new()
+///     .add_chain(Tendermock::new().chain_id("chain-a").add_ephemeral_interface())
+///     .add_chain(Tendermock::new().chain_id("chain-b").add_ephemeral_interface())
+///     .spawn();
+/// let client_id = handle.client_id("chain-a", "chain-b").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Network {
+    chains: Vec<Tendermock>,
+}
+
+impl Network {
+    /// Return a new network, without any chain.
+    pub fn new() -> Self {
+        Network { chains: vec![] }
+    }
+
+    /// Add a chain to the network, chain ids must be unique.
+    pub fn add_chain(&mut self, chain: &Tendermock) -> &mut Self {
+        self.chains.push(chain.clone());
+        self
+    }
+
+    /// Start the network.
+    ///
+    /// This call is blocking, see `spawn` and `run` for non-blocking alternatives.
+    pub fn start(&self) {
         tokio::runtime::Runtime::new()
             .unwrap()
-            .block_on(async {
-                try_join!(
-                    try_join_all(jrpc_servers),
-                    try_join_all(grpc_servers),
-                    schedule_growth(node, self.growth_interval, self.verbose)
-                )
+            .block_on(async { self.run().await.join().await });
+    }
+
+    /// Start the network on a dedicated thread, return once the servers of all the chains are
+    /// bound.
+    pub fn spawn(&self) -> NetworkHandle {
+        let network = self.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(async move {
+                    let NetworkHandle {
+                        chains, clients, ..
+                    } = network.run().await;
+                    let mut tasks = Vec::new();
+                    let mut parts = Vec::new();
+                    for handle in chains {
+                        let TendermockHandle {
+                            interfaces,
+                            node,
+                            shutdown,
+                            server,
+                        } = handle;
+                        if let Server::Task(task) = server {
+                            tasks.push(task);
+                        }
+                        parts.push((interfaces, node, shutdown));
+                    }
+                    sender.send((parts, clients)).unwrap();
+                    join_all(tasks).await;
+                });
+        });
+        let (parts, clients) = receiver.recv().expect("Failed to start the network");
+        let chains = parts
+            .into_iter()
+            .map(|(interfaces, node, shutdown)| TendermockHandle {
+                interfaces,
+                node,
+                shutdown,
+                server: Server::Shared,
             })
-            .unwrap();
+            .collect();
+        NetworkHandle {
+            chains,
+            clients,
+            thread: Some(thread),
+        }
+    }
+
+    /// Start the network on the current Tokio runtime, return once the servers of all the chains
+    /// are bound.
+    pub async fn run(&self) -> NetworkHandle {
+        let nodes = self
+            .chains
+            .iter()
+            .map(|chain| chain.build_node())
+            .collect::<Vec<_>>();
+        let chain_ids = nodes
+            .iter()
+            .map(|node| node.read().get_chain_id().to_string())
+            .collect::<Vec<_>>();
+        for (idx, chain_id) in chain_ids.iter().enumerate() {
+            if chain_ids[..idx].contains(chain_id) {
+                panic!("Duplicated chain id: {}", chain_id);
+            }
+        }
+
+        // Commit the genesis state of the new chains, restored chains are already initialized. The
+        // clients are then built from a header with a real time and application hash, rather than
+        // from the genesis header.
+        let new_chains = nodes
+            .iter()
+            .map(|node| node.read().get_chain().get_height().revision_height == 1)
+            .collect::<Vec<_>>();
+        for (node, new) in nodes.iter().zip(&new_chains) {
+            if *new {
+                node.grow();
+            }
+        }
+
+        // Create the clients of the counterparty chains on the new chains
+        let mut clients = Vec::new();
+        for (host, host_node) in nodes.iter().enumerate() {
+            let mut host_node = host_node.clone();
+            if !new_chains[host] {
+                continue;
+            }
+            for (counterparty, counterparty_node) in nodes.iter().enumerate() {
+                if host == counterparty {
+                    continue;
+                }
+                let (height, header) = {
+                    let node = counterparty_node.read();
+                    let chain = node.get_chain();
+                    let block = chain
+                        .get_block(0)
+                        .expect("The chain should always contain a block");
+                    (chain.get_height(), block.signed_header.header)
+                };
+                let client_id = init::add_counterparty_client(
+                    &mut host_node,
+                    &chain_ids[counterparty],
+                    height,
+                    &header,
+                );
+                if self.chains[host].verbose {
+                    log!(
+                        Log::Chain,
+                        "{}: created client {} of {}",
+                        &chain_ids[host],
+                        &client_id,
+                        &chain_ids[counterparty]
+                    );
+                }
+                clients.push((
+                    chain_ids[host].clone(),
+                    chain_ids[counterparty].clone(),
+                    client_id.to_string(),
+                ));
+            }
+        }
+
+        let chains = self
+            .chains
+            .iter()
+            .zip(nodes)
+            .map(|(chain, node)| chain.serve(node))
+            .collect();
+        NetworkHandle {
+            chains,
+            clients,
+            thread: None,
+        }
     }
 }
 
-/// Push a new block on the chain every `interval` seconds.
-async fn schedule_growth<S: store::Storage>(
-    node: node::SharedNode<S>,
-    interval: u64,
-    verbose: bool,
-) -> Result<(), std::convert::Infallible> {
-    node.grow();
-    if verbose {
-        display_last_block(&node);
+/// A running network, returned by `Network::spawn` and `Network::run`.
+///
+/// Dropping the handle shuts the network down.
+pub struct NetworkHandle {
+    chains: Vec<TendermockHandle>,
+    /// The clients created at genesis, as (host chain id, counterparty chain id, client id).
+    clients: Vec<(String, String, String)>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl NetworkHandle {
+    /// The running chains, in the order they were added to the network.
+    pub fn chains(&self) -> &[TendermockHandle] {
+        &self.chains
+    }
+
+    /// The running chain with id `chain_id`, if any.
+    pub fn chain(&self, chain_id: &str) -> Option<&TendermockHandle> {
+        self.chains
+            .iter()
+            .find(|chain| chain.node.read().get_chain_id().as_str() == chain_id)
+    }
+
+    /// The id of the client of `counterparty` created at genesis on the `host` chain.
+    pub fn client_id(&self, host: &str, counterparty: &str) -> Option<&str> {
+        self.clients
+            .iter()
+            .find(|(h, c, _)| h == host && c == counterparty)
+            .map(|(_, _, client_id)| client_id.as_str())
+    }
+
+    /// Gracefully shut all the chains down.
+    ///
+    /// For a network started with `spawn` this blocks until the servers are stopped, otherwise
+    /// the servers stop in the background.
+    pub fn shutdown(self) {
+        for chain in self.chains {
+            chain.shutdown();
+        }
+        if let Some(thread) = self.thread {
+            thread.join().expect("Network thread panicked");
+        }
+    }
+
+    /// Wait until all the chains stop.
+    async fn join(self) {
+        join_all(self.chains.into_iter().map(|chain| chain.join())).await;
+        if let Some(thread) = self.thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Resolve once a shutdown is requested, or once the shutdown sender is dropped.
+async fn shutdown_signal(mut signal: watch::Receiver<bool>) {
+    while !*signal.borrow() {
+        if signal.changed().await.is_err() {
+            return;
+        }
     }
+}
+
+/// Push a new block on the chain every `interval` seconds, until `shutdown` resolves.
+async fn schedule_growth<S, F>(node: node::SharedNode<S>, interval: u64, verbose: bool, shutdown: F)
+where
+    S: store::Storage,
+    F: Future<Output = ()>,
+{
     if interval == 0 {
-        return Ok(());
-    }
-    loop {
-        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
-        let node_ref = node.write();
-        node_ref.get_chain().grow();
-        drop(node_ref);
-        if verbose {
-            display_last_block(&node);
+        return;
+    }
+    let growth = async {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+            if node.is_paused() {
+                continue;
+            }
+            node.grow();
+            if verbose {
+                display_last_block(&node);
+            }
         }
+    };
+    tokio::select! {
+        _ = growth => {},
+        _ = shutdown => {},
     }
 }
 
 /// Displays the last block of the node's chain.
 fn display_last_block<S: store::Storage>(node: &node::SharedNode<S>) {
     let node = node.read();
-    let block = node.get_chain().get_block(0).unwrap();
-    let header = block.signed_header.header;
+    let header = match node.get_chain().get_block(0) {
+        Some(block) => block.signed_header.header,
+        None => {
+            log!(Log::Chain, "Failed to build the latest block");
+            return;
+        }
+    };
     log!(
         Log::Chain,
         "Height: {} - Hash: {}",
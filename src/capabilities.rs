@@ -0,0 +1,80 @@
+//! # Capabilities
+//!
+//! A minimal capability module, which binds ports to the capabilities handed over to the modules
+//! owning them (such as `transfer`). IBC handlers rely on those capabilities to authenticate the
+//! module before operating on a port.
+//!
+//! Ports can be bound at genesis through the configuration (see `config` module) or at runtime
+//! through the node.
+use ibc::ics05_port::capabilities::Capability;
+use ibc::ics24_host::identifier::PortId;
+use std::collections::HashMap;
+
+/// A registry of the ports bound by the node and their associated capabilities.
+pub struct Capabilities {
+    ports: HashMap<String, Capability>,
+    /// The index of the next capability, each port is handed over a distinct capability.
+    next_index: u64,
+}
+
+impl Capabilities {
+    pub fn new() -> Self {
+        Capabilities {
+            ports: HashMap::new(),
+            // Index 0 is the one of `Capability::new()`, which must not authenticate any port
+            next_index: 1,
+        }
+    }
+
+    /// Bind a port and return the associated capability, or None if the port is already bound.
+    pub fn bind_port(&mut self, port_id: &PortId) -> Option<Capability> {
+        if self.ports.contains_key(port_id.as_str()) {
+            return None;
+        }
+        let capability = Capability::from(self.next_index);
+        self.next_index += 1;
+        self.ports
+            .insert(port_id.as_str().to_owned(), capability.clone());
+        Some(capability)
+    }
+
+    /// Return the capability of a port, if it is bound.
+    pub fn lookup_port(&self, port_id: &PortId) -> Option<Capability> {
+        self.ports.get(port_id.as_str()).cloned()
+    }
+
+    /// Check that the capability is the one owning the port.
+    pub fn authenticate(&self, port_id: &PortId, capability: &Capability) -> bool {
+        match self.ports.get(port_id.as_str()) {
+            Some(owner) => owner.index() == capability.index(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn bind_port() {
+        let mut capabilities = Capabilities::new();
+        let port_id = PortId::from_str("transfer").unwrap();
+        let other_port = PortId::from_str("oracle").unwrap();
+
+        let capability = capabilities.bind_port(&port_id).unwrap();
+        assert!(capabilities.bind_port(&port_id).is_none()); // Already bound
+        assert!(capabilities.authenticate(&port_id, &capability));
+        assert!(!capabilities.authenticate(&other_port, &capability));
+        assert!(!capabilities.authenticate(&port_id, &Capability::new()));
+        assert!(capabilities.lookup_port(&port_id).is_some());
+        assert!(capabilities.lookup_port(&other_port).is_none());
+
+        // Each port has its own capability
+        let other_capability = capabilities.bind_port(&other_port).unwrap();
+        assert!(capabilities.authenticate(&other_port, &other_capability));
+        assert!(!capabilities.authenticate(&other_port, &capability));
+        assert!(!capabilities.authenticate(&port_id, &other_capability));
+    }
+}
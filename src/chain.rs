@@ -2,45 +2,249 @@
 //!
 //! This modules defines the tendermock chain. The chain is a vector of light blocks, which are
 //! stripped down versions of 'real' tendermint blocks.
-use crate::store::Storage;
+//!
+//! The headers commit to the state of the store: the application hash of the header at height `H`
+//! is the root hash of the multistore after block `H - 1`, as in Tendermint. Because the headers
+//! generated by `tendermint_testgen` do not include an application hash, the commits are signed
+//! again once the header has been completed.
+//!
+//! Blocks are pruned together with the store, following the store's `Pruning` policy. Each
+//! commited block is also attached to the state of its height (see `Storage::set_block_data`),
+//! so that a persistent store can restore the exact same blocks after a restart.
+//!
+//! The validator set may change over time, changes are scheduled at a given height and are
+//! reflected in the `next_validators` of the block preceding that height.
+//!
+//! To test misbehaviour handling, the chain can also be forked: a fork is a second header at the
+//! same height, signed by the same validators, but committing to a different state or time.
+//!
+//! The time of the blocks is given by a `Clock`, which can either follow the wall-clock time, add
+//! a fixed step between blocks or be set manually. In any case the time of a block is at least
+//! one second after its predecessor, as required by Tendermint.
+//!
+//! Delivered transactions are included in the pending block, the `data_hash` of the headers
+//! commits to them. Once the block is commited its transactions are indexed, and published
+//! together with the block on the event bus of the chain. The transactions are persisted with
+//! their block, the index is thus rebuilt when the chain is restored, and they are removed from
+//! the index once their block is pruned.
+use crate::config;
+use crate::events::{tx_hash, Event, EventBus, Query, TxEvent, TxIndex};
+use crate::logger::Log;
+use crate::store::{multistore, Storage};
+use ed25519_dalek::Signer;
 use ibc::Height;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::sync::RwLock;
+use tendermint::abci::transaction::Hash;
+use tendermint::abci::Transaction;
+use tendermint::block::{CommitSig, Header as TMHeader};
+use tendermint::merkle::simple_hash_from_byte_vectors;
+use tendermint::signature::Signature;
+use tendermint::vote::{ValidatorIndex, Vote};
 use tendermint::Block as TMBlock;
+use tendermint_rpc::endpoint::broadcast::tx_commit::TxResult;
 use tendermint_testgen::light_block::TMLightBlock;
-use tendermint_testgen::{Generator, LightBlock};
+use tendermint_testgen::{Commit, Generator, Header, LightBlock, Validator};
 
 pub struct Chain<S: Storage> {
     blocks: RwLock<Blocks>,
     store: S,
+    events: EventBus,
+    txs: RwLock<TxIndex>,
 }
 
 struct Blocks {
-    /// The chain of validated blocks.
-    chain: Vec<LightBlock>,
+    /// The chain of validated blocks, pruned blocks are set to None.
+    chain: Vec<Option<Block>>,
     /// The next block candidate, it will be considered valid once another block is added.
     pending_block: LightBlock,
+    /// The transactions delivered in the pending block.
+    pending_txs: Vec<TxEvent>,
+    /// Scheduled changes of the validator set.
+    validator_changes: ValidatorChanges,
+    /// The source of time for new blocks.
+    clock: Clock,
+}
+
+/// The way a forked header differs from the canonical one.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fork {
+    /// The fork commits to a different application hash.
+    AppHash,
+    /// The fork is one second later than the canonical header.
+    Time,
+}
+
+impl Default for Fork {
+    fn default() -> Self {
+        Fork::AppHash
+    }
+}
+
+/// The source of time of the chain, in seconds since the unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// The wall-clock time.
+    Real,
+    /// The genesis is created at `start`, then each block is `step` seconds after the previous.
+    FixedStep { start: u64, step: u64 },
+    /// A time that only changes when set manually.
+    Manual { time: u64 },
+}
+
+impl Clock {
+    /// Return the time of the genesis block.
+    fn genesis_time(&self) -> u64 {
+        match *self {
+            Clock::Real => 1,
+            Clock::FixedStep { start, .. } => start,
+            Clock::Manual { time } => time,
+        }
+    }
+
+    /// Return the time of the block following a block created at `previous`.
+    fn next_time(&self, previous: u64) -> u64 {
+        let time = match *self {
+            Clock::Real => std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            Clock::FixedStep { step, .. } => previous + step,
+            Clock::Manual { time } => time,
+        };
+        std::cmp::max(time, previous + 1)
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::Real
+    }
+}
+
+impl From<config::Clock> for Clock {
+    fn from(clock: config::Clock) -> Self {
+        match clock {
+            config::Clock::Real => Clock::Real,
+            config::Clock::FixedStep { start, step } => Clock::FixedStep { start, step },
+            config::Clock::Manual { time } => Clock::Manual { time },
+        }
+    }
+}
+
+impl From<Clock> for config::Clock {
+    fn from(clock: Clock) -> Self {
+        match clock {
+            Clock::Real => config::Clock::Real,
+            Clock::FixedStep { start, step } => config::Clock::FixedStep { start, step },
+            Clock::Manual { time } => config::Clock::Manual { time },
+        }
+    }
+}
+
+/// Changes of the validator set, indexed by the height at which they take effect. A validator
+/// with a voting power of 0 is removed from the set.
+pub type ValidatorChanges = BTreeMap<u64, Vec<Validator>>;
+
+/// A validated block.
+#[derive(Serialize, Deserialize)]
+struct Block {
+    light_block: LightBlock,
+    /// The application hash, kept here as the state it commits to may have been pruned.
+    app_hash: Vec<u8>,
+    txs: Vec<TxEvent>,
 }
 
 impl<S: Storage> Chain<S> {
-    pub fn new(store: S) -> Self {
-        // To ease testing, the second block is always created at midnight, this fixes the second
-        // header until next midnight.
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let midnight = now - (now % 86_400);
+    /// Create a new chain, whose blocks are signed by `validators`, which are then updated
+    /// according to `validator_changes`.
+    pub fn new(
+        store: S,
+        chain_id: &str,
+        validators: &[Validator],
+        validator_changes: ValidatorChanges,
+        clock: Clock,
+    ) -> Self {
         // Create genesis and pending block
-        let genesis = LightBlock::new_default(1);
-        let mut pending = genesis.next();
-        let mut header_ref = pending.header.as_mut().unwrap();
-        header_ref.time = Some(midnight);
+        let genesis_time = clock.genesis_time();
+        let header = Header::new(validators)
+            .next_validators(validators)
+            .chain_id(chain_id)
+            .height(1)
+            .time(genesis_time);
+        let commit = Commit::new(header.clone(), 1);
+        let mut genesis = LightBlock::new(header, commit);
+        update_next_validators(&mut genesis, &validator_changes);
+        let pending_time = match clock {
+            // To ease testing, the second block is always created at midnight with the real
+            // clock, this fixes the second header until next midnight.
+            Clock::Real => {
+                let now = clock.next_time(genesis_time);
+                now - (now % 86_400)
+            }
+            _ => clock.next_time(genesis_time),
+        };
+        let mut pending = next_block(&genesis, pending_time, &validator_changes);
+        let mut chain = vec![Some(Block {
+            light_block: genesis,
+            app_hash: vec![],
+            txs: vec![],
+        })];
+        // A persistent store may already contain commited blocks, the chain needs to catch up.
+        // The blocks are restored from the data attached to the store, so that their headers are
+        // left unchanged. Blocks without data are rebuilt using the clock.
+        let latest = store.height();
+        while (chain.len() as u64) < latest {
+            let height = chain.len() as u64 + 1;
+            let restored = store
+                .get_block_data(height)
+                .and_then(|data| serde_json::from_slice::<Block>(&data).ok());
+            let block = match restored {
+                Some(block) => {
+                    let time = clock.next_time(block_time(&block.light_block));
+                    pending = next_block(&block.light_block, time, &validator_changes);
+                    Some(block)
+                }
+                None => {
+                    let time = clock.next_time(block_time(&pending));
+                    let mut next_block = next_block(&pending, time, &validator_changes);
+                    std::mem::swap(&mut pending, &mut next_block);
+                    // Blocks whose state has already been pruned can not be recovered
+                    if store.pruning().keeps(height, latest) && !store.is_pruned(height - 1) {
+                        Some(Block {
+                            light_block: next_block,
+                            app_hash: app_hash(&store, height - 1),
+                            txs: vec![],
+                        })
+                    } else {
+                        None
+                    }
+                }
+            };
+            chain.push(block);
+        }
+        // The transactions of the restored blocks are indexed again
+        let mut index = TxIndex::new();
+        for block in chain.iter().flatten() {
+            for tx in &block.txs {
+                index.insert(tx.clone());
+            }
+        }
         Chain {
             blocks: RwLock::new(Blocks {
-                chain: vec![genesis],
+                chain,
                 pending_block: pending,
+                pending_txs: vec![],
+                validator_changes,
+                clock,
             }),
             store,
+            events: EventBus::new(),
+            txs: RwLock::new(index),
         }
     }
 
@@ -54,63 +258,338 @@ impl<S: Storage> Chain<S> {
     /// The height is defined as the height of the latest validated blocks.
     pub fn get_height(&self) -> Height {
         let blocks = &self.blocks.read().unwrap().chain;
-        let height = blocks
-            .last()
-            .expect("[Internal] Chain should be initialized with a block.")
-            .height();
-        Height::new(1, height)
+        Height::new(1, blocks.len() as u64)
     }
 
-    /// Returns a Tendermint Light Block or None if no block exist at that height.
+    /// Returns a Tendermint Light Block or None if no block exist at that height, or if it has
+    /// been pruned.
     pub fn get_block(&self, height: u64) -> Option<TMLightBlock> {
+        self.build_block(height, None).map(|(block, _)| block)
+    }
+
+    /// Returns a Tendermint Block, including its transactions, or None if no block exist at that
+    /// height or if it has been pruned.
+    pub fn get_full_block(&self, height: u64) -> Option<TMBlock> {
+        let (block, txs) = self.build_block(height, None)?;
+        Some(to_full_block(block, txs))
+    }
+
+    /// Returns a signed Tendermint Light Block conflicting with the canonical block at `height`,
+    /// or None if no block exist at that height.
+    pub fn fork(&self, height: u64, fork: Fork) -> Option<TMLightBlock> {
+        self.build_block(height, Some(fork)).map(|(block, _)| block)
+    }
+
+    /// Generate and sign the block at `height`, which is altered if `fork` is set. Returns the
+    /// block together with its transactions.
+    fn build_block(
+        &self,
+        height: u64,
+        fork: Option<Fork>,
+    ) -> Option<(TMLightBlock, Vec<Transaction>)> {
         let chain = &self.blocks.read().unwrap();
-        let block = Chain::<S>::get_block_at_height(height, &chain.chain, &chain.pending_block)?;
-        block.generate().ok()
+        let latest = chain.chain.len() as u64;
+        let height = if height == 0 { latest } else { height };
+        let (block, app_hash, txs) = if height == latest + 1 {
+            // Preview of the next (not yet validated) block
+            let txs = chain.pending_txs.iter().map(|tx| tx.tx.clone()).collect();
+            (&chain.pending_block, self.get_app_hash(latest), txs)
+        } else {
+            let block = chain.chain.get(height.checked_sub(1)? as usize)?.as_ref()?;
+            let txs = block.txs.iter().map(|tx| tx.tx.clone()).collect();
+            (&block.light_block, block.app_hash.clone(), txs)
+        };
+        let forked_block;
+        let (block, app_hash) = match fork {
+            None => (block, app_hash),
+            Some(Fork::AppHash) => (block, Sha256::digest(&app_hash).to_vec()),
+            Some(Fork::Time) => {
+                let mut header = block.header.clone()?;
+                header.time = Some(header.time.unwrap_or_default() + 1);
+                let mut block = block.clone();
+                block.commit = Some(Commit::new(header.clone(), 1));
+                block.header = Some(header);
+                forked_block = block;
+                (&forked_block, app_hash)
+            }
+        };
+        let mut light_block = block.generate().ok()?;
+        let header = &mut light_block.signed_header.header;
+        header.app_hash = tendermint::AppHash::try_from(app_hash).ok()?;
+        header.data_hash = Some(data_hash(&txs));
+        let validators = block.header.as_ref()?.validators.as_ref()?;
+        sign_commit(&mut light_block, validators)?;
+        Some((light_block, txs))
+    }
+
+    /// Returns the application hash after the execution of the block at `height`, that is the
+    /// root hash of the multistore.
+    ///
+    /// There is no state before the genesis block, the application hash is then empty.
+    pub fn get_app_hash(&self, height: u64) -> Vec<u8> {
+        app_hash(&self.store, height)
+    }
+
+    /// Returns true if the block at `height` has been pruned.
+    pub fn is_pruned(&self, height: u64) -> bool {
+        let blocks = &self.blocks.read().unwrap().chain;
+        match height.checked_sub(1).and_then(|h| blocks.get(h as usize)) {
+            Some(block) => block.is_none(),
+            None => false,
+        }
     }
 
     /// Grow the chain by adding a new block.
     pub fn grow(&self) {
-        // Date of the growth
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
         // Create new block
         let mut blocks = self.blocks.write().unwrap();
-        let mut next_block = blocks.pending_block.next();
-        let mut header_ref = next_block.header.as_mut().unwrap();
-        header_ref.time = Some(now);
-        // Set next_block to pending and push the old pending to the chain
+        let time = blocks.clock.next_time(block_time(&blocks.pending_block));
+        let mut next_block = next_block(&blocks.pending_block, time, &blocks.validator_changes);
+        // Set next_block to pending and push the old pending to the chain, its application hash
+        // is the one of the latest state, which is never pruned.
         std::mem::swap(&mut blocks.pending_block, &mut next_block);
-        blocks.chain.push(next_block);
-        drop(blocks); // Release lock
-                      // Grow the store
+        let latest = blocks.chain.len() as u64;
+        let txs = std::mem::take(&mut blocks.pending_txs);
+        let block = Block {
+            light_block: next_block,
+            app_hash: self.get_app_hash(latest),
+            txs: txs.clone(),
+        };
+        // Commit the block together with the store, then prune the chain accordingly
+        let data = serde_json::to_vec(&block).expect("[Internal] Failed to serialize a block");
+        self.store.set_block_data(data);
+        blocks.chain.push(Some(block));
         self.store.grow();
+        let latest = blocks.chain.len() as u64;
+        let pruned = self.store.pruning().pruned_at(latest);
+        if let Some(height) = pruned {
+            blocks.chain[(height - 1) as usize] = None;
+        }
+        drop(blocks);
+        let mut index = self.txs.write().unwrap();
+        if let Some(height) = pruned {
+            index.remove_height(height);
+        }
+        for tx in &txs {
+            index.insert(tx.clone());
+        }
+        drop(index);
+        if self.events.has_subscribers() {
+            if let Some(block) = self.get_full_block(latest) {
+                self.events.publish(Event::NewBlock(block));
+            }
+            for tx in txs {
+                self.events.publish(Event::Tx(tx));
+            }
+        }
     }
 
-    /// Returns the store at a given height, where 0 means latest.
-    fn get_block_at_height<'a>(
-        height: u64,
-        blocks: &'a Vec<LightBlock>,
-        pending: &'a LightBlock,
-    ) -> Option<&'a LightBlock> {
-        if height == 0 {
-            blocks.last()
-        } else if height == (blocks.len() + 1) as u64 {
-            // Preview of the next (not yet validated) block
-            Some(pending)
-        } else {
-            blocks.get((height - 1) as usize)
+    /// Returns the event bus of the chain.
+    pub fn get_events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Include a delivered transaction in the pending block, return the transaction with its
+    /// height and index within the block.
+    ///
+    /// The transaction is indexed and published once the block is commited.
+    pub fn deliver_tx(&self, tx: Transaction, result: TxResult) -> TxEvent {
+        let mut blocks = self.blocks.write().unwrap();
+        let tx = TxEvent {
+            hash: tx_hash(&tx),
+            height: blocks.chain.len() as u64 + 1,
+            index: blocks.pending_txs.len() as u32,
+            tx,
+            result,
+        };
+        blocks.pending_txs.push(tx.clone());
+        tx
+    }
+
+    /// Returns the delivered transaction with the given hash.
+    pub fn get_tx(&self, hash: &Hash) -> Option<TxEvent> {
+        self.txs.read().unwrap().get(hash).cloned()
+    }
+
+    /// Returns the delivered transactions matching `query`, ordered by height and index.
+    pub fn search_txs(&self, query: &Query) -> Vec<TxEvent> {
+        let txs = self.txs.read().unwrap();
+        txs.search(query).into_iter().cloned().collect()
+    }
+
+    /// Schedule a change of the validator set at `height`, a voting power of 0 removes the
+    /// validator.
+    ///
+    /// The validators of a block are known by its predecessor, the earliest height is thus the
+    /// one following the pending block. Returns false if `height` is too low.
+    pub fn schedule_validator_change(&self, height: u64, validator: Validator) -> bool {
+        let mut blocks = self.blocks.write().unwrap();
+        let pending_height = blocks.chain.len() as u64 + 1;
+        if height <= pending_height {
+            return false;
+        }
+        let blocks = &mut *blocks;
+        blocks
+            .validator_changes
+            .entry(height)
+            .or_insert_with(Vec::new)
+            .push(validator);
+        update_next_validators(&mut blocks.pending_block, &blocks.validator_changes);
+        true
+    }
+
+    /// Switch to a manual clock set at `time`, which becomes the time of the pending block.
+    ///
+    /// The time can not go backward, the pending block is at least one second after the latest
+    /// block. Returns the time of the pending block.
+    pub fn set_time(&self, time: u64) -> u64 {
+        let mut blocks = self.blocks.write().unwrap();
+        let latest = blocks
+            .chain
+            .last()
+            .and_then(|block| block.as_ref())
+            .expect("[Internal] The latest block is never pruned");
+        let time = std::cmp::max(time, block_time(&latest.light_block) + 1);
+        blocks.clock = Clock::Manual { time };
+        let pending = &mut blocks.pending_block;
+        pending.header.as_mut().unwrap().time = Some(time);
+        pending.commit = Some(Commit::new(pending.header.clone().unwrap(), 1));
+        time
+    }
+
+    /// Move the time of the pending block forward by `seconds`, switching to a manual clock.
+    /// Returns the time of the pending block.
+    pub fn advance_time(&self, seconds: u64) -> u64 {
+        let time = block_time(&self.blocks.read().unwrap().pending_block);
+        self.set_time(time + seconds)
+    }
+}
+
+/// Return the time of a block, in seconds since the unix epoch.
+fn block_time(block: &LightBlock) -> u64 {
+    block
+        .header
+        .as_ref()
+        .and_then(|header| header.time)
+        .unwrap_or_default()
+}
+
+/// Build the block following `block`.
+fn next_block(block: &LightBlock, time: u64, changes: &ValidatorChanges) -> LightBlock {
+    let mut next_block = block.next();
+    let mut header_ref = next_block.header.as_mut().unwrap();
+    header_ref.time = Some(time);
+    update_next_validators(&mut next_block, changes);
+    next_block
+}
+
+/// Set the next validators of `block`, by applying the changes scheduled at the next height.
+fn update_next_validators(block: &mut LightBlock, changes: &ValidatorChanges) {
+    let header = block
+        .header
+        .as_mut()
+        .expect("[Internal] Blocks should have a header");
+    let validators = header.validators.clone().unwrap_or_default();
+    let height = header.height.unwrap_or_default() + 1;
+    let next_validators = match changes.get(&height) {
+        Some(changes) => apply_validator_changes(&validators, changes),
+        None => validators,
+    };
+    header.next_validators = Some(next_validators.clone());
+    block.next_validators = Some(next_validators);
+    block.commit = Some(Commit::new(header.clone(), 1));
+}
+
+/// Apply changes to a validator set, the set is left unchanged if it would end up empty.
+fn apply_validator_changes(validators: &[Validator], changes: &[Validator]) -> Vec<Validator> {
+    let mut next_validators = validators.to_vec();
+    for change in changes {
+        next_validators.retain(|v| v.id != change.id);
+        if change.voting_power.unwrap_or_default() > 0 {
+            next_validators.push(change.clone());
         }
     }
+    if next_validators.is_empty() {
+        log!(
+            Log::Chain,
+            "Ignoring validator changes: the set can not be empty"
+        );
+        return validators.to_vec();
+    }
+    next_validators
+}
+
+/// Returns the application hash after the execution of the block at `height`.
+fn app_hash<S: Storage>(store: &S, height: u64) -> Vec<u8> {
+    if height == 0 {
+        return vec![];
+    }
+    multistore(store, height).root_hash()
 }
 
-/// Build a Tendermint block from a Tendermint loght block.
-pub fn to_full_block(light_block: TMLightBlock) -> TMBlock {
+/// Sign the commit of a light block with the private keys of the validators.
+///
+/// This must be called whenever the header is modified, as its hash is part of the signed votes.
+fn sign_commit(light_block: &mut TMLightBlock, validators: &[Validator]) -> Option<()> {
+    let header: &TMHeader = &light_block.signed_header.header;
+    let chain_id = header.chain_id.clone();
+    let commit = &mut light_block.signed_header.commit;
+    commit.block_id.hash = header.hash();
+    let signatures = commit
+        .signatures
+        .iter()
+        .enumerate()
+        .map(|(index, commit_sig)| match commit_sig {
+            CommitSig::BlockIDFlagCommit {
+                validator_address,
+                timestamp,
+                signature,
+            } => {
+                let validator = validators.iter().find(|v| match v.generate() {
+                    Ok(info) => info.address == *validator_address,
+                    Err(_) => false,
+                })?;
+                let keypair = validator.get_private_key().ok()?;
+                let vote = Vote {
+                    vote_type: tendermint::vote::Type::Precommit,
+                    height: commit.height,
+                    round: commit.round,
+                    block_id: Some(commit.block_id.clone()),
+                    timestamp: Some(*timestamp),
+                    validator_address: *validator_address,
+                    validator_index: ValidatorIndex::try_from(index as u32).ok()?,
+                    signature: signature.clone(),
+                };
+                let sign_bytes = vote.to_signable_vec(chain_id.clone()).ok()?;
+                Some(CommitSig::BlockIDFlagCommit {
+                    validator_address: *validator_address,
+                    timestamp: *timestamp,
+                    signature: Signature::Ed25519(keypair.sign(&sign_bytes)),
+                })
+            }
+            other => Some(other.clone()),
+        })
+        .collect::<Option<Vec<CommitSig>>>()?;
+    commit.signatures = signatures.into();
+    Some(())
+}
+
+/// Returns the data hash of a block, that is the merkle root of the hashes of its transactions, as
+/// in Tendermint.
+fn data_hash(txs: &[Transaction]) -> tendermint::Hash {
+    let leaves = txs
+        .iter()
+        .map(|tx| tx_hash(tx).as_bytes().to_vec())
+        .collect();
+    tendermint::Hash::Sha256(simple_hash_from_byte_vectors(leaves))
+}
+
+/// Build a Tendermint block from a Tendermint light block and its transactions.
+fn to_full_block(light_block: TMLightBlock, txs: Vec<Transaction>) -> TMBlock {
     let signed_header = light_block.signed_header;
     let block = tendermint::Block::new(
         signed_header.header,
-        tendermint::abci::transaction::Data::default(), // TODO: should we include transaction data?
+        tendermint::abci::transaction::Data::new(txs),
         tendermint::evidence::Data::new(vec![]),
         Some(signed_header.commit),
     )
@@ -121,11 +600,191 @@ pub fn to_full_block(light_block: TMLightBlock) -> TMBlock {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::store::InMemoryStore;
+    use crate::store::{FileStore, InMemoryStore, Pruning};
+    use tendermint_light_client::components::verifier::{ProdVerifier, Verdict, Verifier};
+    use tendermint_light_client::light_client::Options;
+    use tendermint_light_client::types::LightBlock as LCLightBlock;
+
+    fn new_chain<S: Storage>(store: S) -> Chain<S> {
+        let validators = [
+            Validator::new("alice").voting_power(50),
+            Validator::new("bob").voting_power(30),
+        ];
+        Chain::new(
+            store,
+            "tendermock",
+            &validators,
+            ValidatorChanges::new(),
+            Clock::Real,
+        )
+    }
+
+    fn to_light_client_block(block: TMLightBlock) -> LCLightBlock {
+        LCLightBlock::new(
+            block.signed_header,
+            block.validators,
+            block.next_validators,
+            block.provider,
+        )
+    }
+
+    #[test]
+    fn light_client_verification() {
+        let chain = new_chain(InMemoryStore::new());
+        chain.get_store().set(b"foo".to_vec(), b"bar".to_vec());
+        chain.grow();
+        chain.grow();
+        let trusted = chain.get_block(2).unwrap();
+        let untrusted = chain.get_block(3).unwrap();
+        assert_eq!(
+            untrusted.signed_header.header.chain_id.as_str(),
+            "tendermock"
+        );
+
+        let options = Options {
+            trust_threshold: Default::default(),
+            trusting_period: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+            clock_drift: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+        };
+        let verdict = ProdVerifier::default().verify(
+            &to_light_client_block(untrusted),
+            &to_light_client_block(trusted),
+            &options,
+            tendermint::Time::now(),
+        );
+        assert!(matches!(verdict, Verdict::Success));
+    }
+
+    #[test]
+    fn fork() {
+        let chain = new_chain(InMemoryStore::new());
+        chain.get_store().set(b"foo".to_vec(), b"bar".to_vec());
+        chain.grow();
+        chain.grow();
+        let trusted = to_light_client_block(chain.get_block(2).unwrap());
+        let canonical = chain.get_block(3).unwrap();
+        let options = Options {
+            trust_threshold: Default::default(),
+            trusting_period: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+            clock_drift: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+        };
+
+        for kind in &[Fork::AppHash, Fork::Time] {
+            let fork = chain.fork(3, *kind).unwrap();
+            let header = &fork.signed_header.header;
+            let canonical_header = &canonical.signed_header.header;
+            assert_eq!(header.height, canonical_header.height);
+            assert_ne!(header.hash(), canonical_header.hash());
+            match kind {
+                Fork::AppHash => assert_ne!(header.app_hash, canonical_header.app_hash),
+                Fork::Time => assert_ne!(header.time, canonical_header.time),
+            }
+            // Both headers are valid, which is a misbehaviour
+            let verdict = ProdVerifier::default().verify(
+                &to_light_client_block(fork),
+                &trusted,
+                &options,
+                tendermint::Time::now(),
+            );
+            assert!(matches!(verdict, Verdict::Success));
+        }
+        // The canonical chain is left untouched
+        assert_eq!(
+            chain.get_block(3).unwrap().signed_header.header.hash(),
+            canonical.signed_header.header.hash()
+        );
+    }
+
+    #[test]
+    fn clock() {
+        let time = |chain: &Chain<InMemoryStore>, height| {
+            let time = chain.get_block(height).unwrap().signed_header.header.time;
+            std::time::SystemTime::from(time)
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        let validators = [Validator::new("alice")];
+        let clock = Clock::FixedStep {
+            start: 1_000_000,
+            step: 5,
+        };
+        let chain = Chain::new(
+            InMemoryStore::new(),
+            "tendermock",
+            &validators,
+            ValidatorChanges::new(),
+            clock,
+        );
+        chain.grow();
+        chain.grow();
+        assert_eq!(time(&chain, 1), 1_000_000);
+        assert_eq!(time(&chain, 2), 1_000_005);
+        assert_eq!(time(&chain, 3), 1_000_010);
+
+        // Switch to a manual clock
+        assert_eq!(chain.set_time(2_000_000), 2_000_000);
+        chain.grow();
+        assert_eq!(time(&chain, 4), 2_000_000);
+        // Blocks are at least one second apart, even if the time is not updated
+        chain.grow();
+        assert_eq!(time(&chain, 5), 2_000_001);
+        assert_eq!(chain.advance_time(60), 2_000_062);
+        // The time can not go backward
+        assert_eq!(chain.set_time(0), 2_000_002);
+    }
+
+    #[test]
+    fn validator_changes() {
+        let alice = Validator::new("alice").voting_power(50);
+        let bob = Validator::new("bob").voting_power(30);
+        let mut changes = ValidatorChanges::new();
+        changes.insert(3, vec![bob.clone()]);
+        let validators = [alice.clone()];
+        let chain = Chain::new(
+            InMemoryStore::new(),
+            "tendermock",
+            &validators,
+            changes,
+            Clock::Real,
+        );
+        assert!(chain.schedule_validator_change(4, alice.clone().voting_power(0)));
+        assert!(!chain.schedule_validator_change(2, bob.clone()));
+        chain.grow();
+        chain.grow();
+        chain.grow();
+
+        let validators = |height| {
+            chain
+                .get_block(height)
+                .unwrap()
+                .validators
+                .validators()
+                .len()
+        };
+        assert_eq!(validators(2), 1);
+        assert_eq!(validators(3), 2);
+        assert_eq!(validators(4), 1);
+        // The header commits to the next validator set
+        let block = chain.get_block(2).unwrap();
+        assert_eq!(
+            block.signed_header.header.next_validators_hash,
+            chain
+                .get_block(3)
+                .unwrap()
+                .signed_header
+                .header
+                .validators_hash
+        );
+        assert_ne!(
+            block.signed_header.header.next_validators_hash,
+            block.signed_header.header.validators_hash
+        );
+    }
 
     #[test]
     fn chain() {
-        let chain = Chain::new(InMemoryStore::new());
+        let chain = new_chain(InMemoryStore::new());
         let height = chain.get_height();
 
         // Chain is expected to start at height 1 (same as Storage)
@@ -139,4 +798,186 @@ mod test {
         let height = chain.get_height();
         assert_eq!(height.revision_height, 3); // Now the third block is valid
     }
+
+    #[test]
+    fn app_hash() {
+        let chain = new_chain(InMemoryStore::new());
+        chain.get_store().set(b"foo".to_vec(), b"bar".to_vec());
+        chain.grow();
+        let app_hash = chain.get_app_hash(2);
+        assert!(!app_hash.is_empty());
+        // The header at height 3 commits to the state after block 2
+        let block = chain.get_block(3).unwrap();
+        assert_eq!(
+            block.signed_header.header.app_hash.as_ref(),
+            app_hash.as_slice()
+        );
+        assert_eq!(
+            block.signed_header.commit.block_id.hash,
+            block.signed_header.header.hash()
+        );
+        // The genesis block does not commit to any state
+        let genesis = chain.get_block(1).unwrap();
+        assert!(genesis.signed_header.header.app_hash.as_ref().is_empty());
+    }
+
+    #[test]
+    fn pruning() {
+        let chain = new_chain(InMemoryStore::with_pruning(Pruning::KeepRecent(2)));
+        let tx = chain.deliver_tx(Transaction::new(b"foo".to_vec()), tx_result());
+        for i in 0..4u8 {
+            chain.get_store().set(b"foo".to_vec(), vec![i]);
+            chain.grow();
+        }
+        // The transactions of pruned blocks are no longer indexed
+        assert!(chain.is_pruned(2));
+        assert!(chain.get_tx(&tx.hash).is_none());
+        assert_eq!(chain.get_height().revision_height, 5);
+        assert!(chain.is_pruned(3));
+        assert!(chain.get_block(3).is_none());
+        assert!(!chain.is_pruned(1));
+        assert!(chain.get_block(1).is_some());
+        // The oldest kept block still commits to the (pruned) state of its parent
+        assert!(chain.get_store().is_pruned(3));
+        let block = chain.get_block(4).unwrap();
+        assert!(!block.signed_header.header.app_hash.as_ref().is_empty());
+    }
+
+    #[test]
+    fn restart() {
+        let path = std::env::temp_dir().join(format!(
+            "tendermock-chain-restart-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let open = || FileStore::open(&path, Pruning::KeepAll).unwrap();
+        let chain = new_chain(open());
+        chain.get_store().set(b"foo".to_vec(), b"bar".to_vec());
+        let tx = chain.deliver_tx(Transaction::new(b"foo".to_vec()), tx_result());
+        chain.grow();
+        chain.set_time(2_000_000_000);
+        chain.grow();
+        chain.grow();
+        let hashes = (1..=4)
+            .map(|h| chain.get_block(h).unwrap().signed_header.header.hash())
+            .collect::<Vec<_>>();
+        drop(chain);
+
+        // The restored headers are identical, even though the clock moved on
+        let chain = new_chain(open());
+        assert_eq!(chain.get_height().revision_height, 4);
+        for (height, hash) in (1..=4).zip(hashes) {
+            assert_eq!(
+                chain.get_block(height).unwrap().signed_header.header.hash(),
+                hash
+            );
+        }
+        // The transactions are restored, and indexed again
+        let block = chain.get_full_block(2).unwrap();
+        assert_eq!(block.data.iter().collect::<Vec<_>>(), vec![&tx.tx]);
+        assert_eq!(chain.get_tx(&tx.hash).unwrap().height, 2);
+        // The chain keeps growing on top of the restored blocks
+        chain.grow();
+        let trusted = to_light_client_block(chain.get_block(4).unwrap());
+        let untrusted = to_light_client_block(chain.get_block(5).unwrap());
+        let options = Options {
+            trust_threshold: Default::default(),
+            trusting_period: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+            clock_drift: std::time::Duration::from_secs(3600 * 24 * 365 * 100),
+        };
+        let verdict =
+            ProdVerifier::default().verify(&untrusted, &trusted, &options, tendermint::Time::now());
+        assert!(matches!(verdict, Verdict::Success));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn new_block_events() {
+        let chain = new_chain(InMemoryStore::new());
+        chain.grow();
+        let mut events = chain.get_events().subscribe();
+        chain.grow();
+        match events.try_recv().unwrap() {
+            Event::NewBlock(block) => {
+                assert_eq!(block.header.height.value(), 3);
+                let header = chain.get_block(3).unwrap().signed_header.header;
+                assert_eq!(block.header.hash(), header.hash());
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    fn tx_result() -> TxResult {
+        TxResult {
+            code: tendermint::abci::Code::Ok,
+            data: None,
+            log: "Success".into(),
+            codespace: Default::default(),
+            gas_used: 10.into(),
+            gas_wanted: 10.into(),
+            info: Default::default(),
+            events: vec![],
+        }
+    }
+
+    #[test]
+    fn tx_index() {
+        let chain = new_chain(InMemoryStore::new());
+        let mut hashes = Vec::new();
+        for i in 0..3u8 {
+            let tx = chain.deliver_tx(Transaction::new(vec![i]), tx_result());
+            assert_eq!(tx.height, i as u64 + 2);
+            hashes.push(tx.hash);
+            // Transactions are indexed once commited
+            assert!(chain.get_tx(&tx.hash).is_none());
+            chain.grow();
+        }
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(chain.get_tx(&hashes[1]).unwrap().height, 3);
+        let query = Query::parse("tx.height>2").unwrap();
+        let heights = chain
+            .search_txs(&query)
+            .iter()
+            .map(|tx| tx.height)
+            .collect::<Vec<_>>();
+        assert_eq!(heights, vec![3, 4]);
+    }
+
+    #[test]
+    fn block_txs() {
+        let chain = new_chain(InMemoryStore::new());
+        let mut events = chain.get_events().subscribe();
+        let txs = vec![
+            Transaction::new(b"foo".to_vec()),
+            Transaction::new(b"bar".to_vec()),
+        ];
+        for (index, tx) in txs.iter().enumerate() {
+            let tx = chain.deliver_tx(tx.clone(), tx_result());
+            assert_eq!(tx.index, index as u32);
+        }
+        chain.grow();
+        let block = chain.get_full_block(2).unwrap();
+        assert_eq!(block.data.iter().cloned().collect::<Vec<_>>(), txs);
+        assert_eq!(block.header.data_hash, Some(data_hash(&txs)));
+        assert_ne!(data_hash(&txs), data_hash(&[]));
+        // The header commiting to the transactions is signed
+        let light_block = chain.get_block(2).unwrap();
+        assert_eq!(light_block.signed_header.header.hash(), block.header.hash());
+        assert_eq!(
+            light_block.signed_header.commit.block_id.hash,
+            block.header.hash()
+        );
+        // The block is published before its transactions
+        assert!(matches!(events.try_recv().unwrap(), Event::NewBlock(_)));
+        assert!(matches!(events.try_recv().unwrap(), Event::Tx(_)));
+        assert!(matches!(events.try_recv().unwrap(), Event::Tx(_)));
+        assert!(chain
+            .get_full_block(3)
+            .unwrap()
+            .data
+            .iter()
+            .next()
+            .is_none());
+    }
 }
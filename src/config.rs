@@ -6,33 +6,167 @@
 //! For now the configuration options are rather limited, but those can be extended by adding new
 //! fields to the `Config` structs.
 //!
-//! The deserialization uses `serde` and `serde_json` to define and parse json config files.
-use serde_json;
+//! The deserialization uses `serde` and `serde_json` to define and parse json config files, the
+//! configuration is then validated so that invalid values are reported when loading it rather than
+//! when starting the node.
+//!
+//! The configuration types do not depend on the rest of the crate, the runtime types (such as
+//! `store::Pruning` and `chain::Clock`) implement the conversions from and into them.
+use ibc::ics24_host::identifier::PortId;
 use serde::Deserialize;
+use serde_json;
+use std::fmt;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
 use tendermint;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct Config {
     pub chain_id: String,
     pub host_client: Client,
     pub clients: Vec<Client>,
+    pub validators: Vec<Validator>,
+    pub validator_changes: Vec<ValidatorChange>,
+    pub ports: Vec<String>,
+    pub storage: Storage,
+    pub pruning: Pruning,
+    pub clock: Clock,
     pub consensus_params: tendermint::consensus::Params,
 }
 
-#[derive(Deserialize)]
+/// The kind of storage backing the node.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum Storage {
+    /// A volatile, in-memory storage.
+    InMemory,
+    /// A persistent storage, backed by the file at the given path.
+    File(String),
+}
+
+/// The historical heights kept by the store.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum Pruning {
+    /// Keep every height.
+    KeepAll,
+    /// Keep only the `N` last commited heights, `N` must be positive.
+    KeepRecent(u64),
+    /// Keep only the heights that are a multiple of `K`, `K` must be positive.
+    KeepEvery(u64),
+}
+
+/// The source of time of the chain, in seconds since the unix epoch.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum Clock {
+    /// The wall-clock time.
+    Real,
+    /// The genesis is created at `start`, then each block is `step` seconds after the previous.
+    FixedStep { start: u64, step: u64 },
+    /// A time that only changes when set manually.
+    Manual { time: u64 },
+}
+
+#[derive(Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Client {
     pub id: String,
 }
 
+/// A validator, its ed25519 key is derived from its id.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Validator {
+    pub id: String,
+    pub voting_power: u64,
+}
+
+/// A change of the validator set at a given height: the validator is either added, updated or
+/// removed if its voting power is 0.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ValidatorChange {
+    pub height: u64,
+    pub id: String,
+    pub voting_power: u64,
+}
+
+/// An error raised while loading or validating the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config file: {}", e),
+            ConfigError::Parse(e) => write!(f, "could not parse config file: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl Config {
-    pub fn load<P: AsRef<Path>>(path: P) -> Self {
-        let config = fs::read_to_string(&path)
-            .expect(&format!("Could not find file: {}", path.as_ref().display()));
-        serde_json::from_str(&config).expect("Could not parse config file")
+    /// Load and validate a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let config = fs::read_to_string(&path).map_err(ConfigError::Io)?;
+        let config: Config = serde_json::from_str(&config).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the values that can not be enforced by the deserialization.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for port in &self.ports {
+            PortId::from_str(port)
+                .map_err(|_| ConfigError::Invalid(format!("invalid port id: {}", port)))?;
+        }
+        if self.validators.is_empty() {
+            return Err(ConfigError::Invalid(String::from(
+                "at least one validator is required",
+            )));
+        }
+        let mut ids = std::collections::HashSet::new();
+        for validator in &self.validators {
+            if !ids.insert(&validator.id) {
+                return Err(ConfigError::Invalid(format!(
+                    "duplicate validator id: {}",
+                    validator.id
+                )));
+            }
+            if validator.voting_power == 0 {
+                return Err(ConfigError::Invalid(format!(
+                    "validator {} has no voting power",
+                    validator.id
+                )));
+            }
+        }
+        // The validators of the genesis block are given by `validators`
+        for change in &self.validator_changes {
+            if change.height <= 1 {
+                return Err(ConfigError::Invalid(format!(
+                    "validator change of {} at height {}, changes must happen after genesis",
+                    change.id, change.height
+                )));
+            }
+        }
+        match self.pruning {
+            Pruning::KeepRecent(0) | Pruning::KeepEvery(0) => {
+                return Err(ConfigError::Invalid(String::from(
+                    "pruning would not keep any historical height",
+                )))
+            }
+            _ => (),
+        }
+        Ok(())
     }
 }
 
@@ -44,6 +178,15 @@ impl Default for Config {
                 id: String::from("donald_duck"),
             },
             clients: vec![],
+            validators: vec![Validator {
+                id: String::from("tendermock"),
+                voting_power: 50,
+            }],
+            validator_changes: vec![],
+            ports: vec![String::from("transfer")],
+            storage: Storage::InMemory,
+            pruning: Pruning::KeepAll,
+            clock: Clock::Real,
             consensus_params: default_params(),
         }
     }
@@ -66,3 +209,60 @@ fn default_params() -> tendermint::consensus::Params {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        config.ports.push(String::from("not a port!"));
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn validate_validators() {
+        let validator = |id: &str, voting_power| Validator {
+            id: String::from(id),
+            voting_power,
+        };
+        let mut config = Config::default();
+        config.validators = vec![];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+        config.validators = vec![validator("alice", 10), validator("bob", 0)];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+        config.validators = vec![validator("alice", 10), validator("alice", 20)];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+        config.validators = vec![validator("alice", 10), validator("bob", 20)];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_validator_changes() {
+        let mut config = Config::default();
+        let change = |height| ValidatorChange {
+            height,
+            id: String::from("alice"),
+            voting_power: 10,
+        };
+        config.validator_changes = vec![change(2)];
+        assert!(config.validate().is_ok());
+        config.validator_changes.push(change(1));
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn validate_pruning() {
+        let mut config = Config::default();
+        for pruning in &[Pruning::KeepRecent(1), Pruning::KeepEvery(10)] {
+            config.pruning = *pruning;
+            assert!(config.validate().is_ok());
+        }
+        for pruning in &[Pruning::KeepRecent(0), Pruning::KeepEvery(0)] {
+            config.pruning = *pruning;
+            assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+        }
+    }
+}
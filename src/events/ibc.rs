@@ -0,0 +1,226 @@
+//! # IBC events
+//!
+//! Conversion of the events emitted by the IBC handlers into ABCI events, with the types and
+//! attribute keys used by the Cosmos SDK (`create_client`, `connection_open_init`, `send_packet`,
+//! ...), which are the ones relayers look for.
+//!
+//! The event types are given by matching on the `IbcEvent` variants, so that a change upstream
+//! breaks the build rather than the relayers. Packets are converted field by field, their data is
+//! emitted both as a string and hex-encoded (`packet_data_hex`), as in the Cosmos SDK. The other
+//! events hold plain attributes (identifiers and heights), which are flattened from their
+//! serialized form. Heights are formatted as `revision-height`.
+use ibc::events::IbcEvent;
+use ibc::ics02_client::client_type::ClientType;
+use ibc::ics04_channel::packet::Packet;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tendermint::abci::tag::Tag;
+use tendermint::abci::Event as AbciEvent;
+
+/// Convert the events emitted by the IBC handlers into ABCI events.
+///
+/// Each IBC event is preceded by a `message` event, whose `action` is the type of the IBC event,
+/// as the Cosmos SDK does for each message.
+pub fn to_abci_events(events: &[IbcEvent]) -> Vec<AbciEvent> {
+    let mut abci_events = Vec::new();
+    for event in events {
+        let (event_type, attributes) = match to_abci_attributes(event) {
+            Some(event) => event,
+            None => continue,
+        };
+        abci_events.push(new_event(
+            "message",
+            vec![(String::from("action"), String::from(event_type))],
+        ));
+        abci_events.push(new_event(event_type, attributes));
+    }
+    abci_events
+}
+
+/// Return the type and attributes of an event, or None if the event is not a transaction event.
+fn to_abci_attributes(event: &IbcEvent) -> Option<(&'static str, Vec<(String, String)>)> {
+    let event = match event {
+        IbcEvent::CreateClient(e) => ("create_client", attributes(e)),
+        IbcEvent::UpdateClient(e) => ("update_client", attributes(e)),
+        IbcEvent::ClientMisbehavior(e) => ("client_misbehaviour", attributes(e)),
+        IbcEvent::OpenInitConnection(e) => ("connection_open_init", attributes(e)),
+        IbcEvent::OpenTryConnection(e) => ("connection_open_try", attributes(e)),
+        IbcEvent::OpenAckConnection(e) => ("connection_open_ack", attributes(e)),
+        IbcEvent::OpenConfirmConnection(e) => ("connection_open_confirm", attributes(e)),
+        IbcEvent::OpenInitChannel(e) => ("channel_open_init", attributes(e)),
+        IbcEvent::OpenTryChannel(e) => ("channel_open_try", attributes(e)),
+        IbcEvent::OpenAckChannel(e) => ("channel_open_ack", attributes(e)),
+        IbcEvent::OpenConfirmChannel(e) => ("channel_open_confirm", attributes(e)),
+        IbcEvent::CloseInitChannel(e) => ("channel_close_init", attributes(e)),
+        IbcEvent::CloseConfirmChannel(e) => ("channel_close_confirm", attributes(e)),
+        IbcEvent::SendPacketChannel(e) => ("send_packet", packet_attributes(&e.packet)),
+        IbcEvent::ReceivePacketChannel(e) => ("recv_packet", packet_attributes(&e.packet)),
+        IbcEvent::WriteAcknowledgementChannel(e) => {
+            let mut attributes = packet_attributes(&e.packet);
+            attributes.push(pair("packet_ack", String::from_utf8_lossy(&e.ack)));
+            attributes.push(pair("packet_ack_hex", hex::encode(&e.ack)));
+            ("write_acknowledgement", attributes)
+        }
+        IbcEvent::AcknowledgePacketChannel(e) => {
+            ("acknowledge_packet", packet_attributes(&e.packet))
+        }
+        IbcEvent::TimeoutPacketChannel(e) => ("timeout_packet", packet_attributes(&e.packet)),
+        IbcEvent::NewBlock(_) | IbcEvent::Empty(_) | IbcEvent::ChainError(_) => return None,
+    };
+    Some(event)
+}
+
+/// Return the attributes of a packet, with the keys of the Cosmos SDK.
+fn packet_attributes(packet: &Packet) -> Vec<(String, String)> {
+    vec![
+        pair("packet_data", String::from_utf8_lossy(&packet.data)),
+        pair("packet_data_hex", hex::encode(&packet.data)),
+        pair("packet_timeout_height", packet.timeout_height.to_string()),
+        pair(
+            "packet_timeout_timestamp",
+            packet.timeout_timestamp.to_string(),
+        ),
+        pair("packet_sequence", packet.sequence.to_string()),
+        pair("packet_src_port", packet.source_port.to_string()),
+        pair("packet_src_channel", packet.source_channel.to_string()),
+        pair("packet_dst_port", packet.destination_port.to_string()),
+        pair("packet_dst_channel", packet.destination_channel.to_string()),
+    ]
+}
+
+/// Flatten the serialized attributes of an event, None values are skipped.
+fn attributes<E: Serialize>(event: &E) -> Vec<(String, String)> {
+    let fields = match serde_json::to_value(event) {
+        Ok(Value::Object(fields)) => fields,
+        _ => return vec![],
+    };
+    let mut attributes = Vec::new();
+    for (key, value) in fields {
+        // The height of the event is the one of the transaction
+        if key == "height" {
+            continue;
+        }
+        let value = if key == "client_type" {
+            // Client types are formatted as identifiers, such as `07-tendermint`
+            serde_json::from_value::<ClientType>(value)
+                .ok()
+                .map(|client_type| client_type.as_string().to_owned())
+        } else {
+            to_attribute_value(value)
+        };
+        if let Some(value) = value {
+            attributes.push((key, value));
+        }
+    }
+    attributes
+}
+
+/// Format a serialized identifier or height as an attribute value.
+fn to_attribute_value(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(object) => Some(format_object(object)),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Format heights as `revision-height`, other objects as JSON.
+fn format_object(object: Map<String, Value>) -> String {
+    let number = object.get("revision_number").and_then(Value::as_u64);
+    let height = object.get("revision_height").and_then(Value::as_u64);
+    match (number, height) {
+        (Some(number), Some(height)) if object.len() == 2 => format!("{}-{}", number, height),
+        _ => Value::Object(object).to_string(),
+    }
+}
+
+fn pair<V: Into<String>>(key: &str, value: V) -> (String, String) {
+    (String::from(key), value.into())
+}
+
+fn new_event(event_type: &str, attributes: Vec<(String, String)>) -> AbciEvent {
+    AbciEvent {
+        type_str: String::from(event_type),
+        attributes: attributes
+            .into_iter()
+            .map(|(key, value)| Tag {
+                key: key.parse().unwrap(),
+                value: value.parse().unwrap(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ibc::ics02_client::events::{Attributes, CreateClient};
+    use ibc::ics04_channel::events::SendPacket;
+    use ibc::ics04_channel::packet::Sequence;
+    use ibc::ics24_host::identifier::{ChannelId, ClientId, PortId};
+    use ibc::Height;
+    use std::str::FromStr;
+
+    fn attributes(event: &AbciEvent) -> Vec<(String, String)> {
+        event
+            .attributes
+            .iter()
+            .map(|tag| (tag.key.to_string(), tag.value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn ibc_events() {
+        let height = Height::new(1, 5);
+        let create_client = CreateClient::from(Attributes {
+            height,
+            client_id: ClientId::from_str("07-tendermint-0").unwrap(),
+            client_type: ClientType::Tendermint,
+            consensus_height: height,
+        });
+        let packet = Packet {
+            sequence: Sequence::from(1),
+            source_port: PortId::from_str("transfer").unwrap(),
+            source_channel: ChannelId::from_str("channel-0").unwrap(),
+            destination_port: PortId::from_str("transfer").unwrap(),
+            destination_channel: ChannelId::from_str("channel-1").unwrap(),
+            data: vec![0xff, 0x00, b'a'],
+            timeout_height: Height::new(1, 10),
+            timeout_timestamp: 0,
+        };
+        let events = to_abci_events(&[
+            IbcEvent::CreateClient(create_client),
+            IbcEvent::Empty(String::new()),
+            IbcEvent::SendPacketChannel(SendPacket { height, packet }),
+        ]);
+        let types = events
+            .iter()
+            .map(|e| e.type_str.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec!["message", "create_client", "message", "send_packet"]
+        );
+        assert_eq!(
+            attributes(&events[0]),
+            vec![pair("action", "create_client")]
+        );
+        assert_eq!(
+            attributes(&events[1]),
+            vec![
+                pair("client_id", "07-tendermint-0"),
+                pair("client_type", "07-tendermint"),
+                pair("consensus_height", "1-5"),
+            ]
+        );
+        let packet_attributes = attributes(&events[3]);
+        assert!(packet_attributes.contains(&pair("packet_data_hex", "ff0061")));
+        assert!(packet_attributes.contains(&pair("packet_sequence", "1")));
+        assert!(packet_attributes.contains(&pair("packet_src_port", "transfer")));
+        assert!(packet_attributes.contains(&pair("packet_dst_channel", "channel-1")));
+        assert!(packet_attributes.contains(&pair("packet_timeout_height", "1-10")));
+    }
+}
@@ -0,0 +1,197 @@
+//! # Transaction index
+//!
+//! An in-memory index of the delivered transactions, as used by the `/tx` and `/tx_search`
+//! endpoints. Transactions are indexed by hash, and by the composite keys of their events: a
+//! search only evaluates the query against the transactions holding its rarest key.
+//!
+//! The index is not persisted itself, it is rebuilt from the commited blocks when the chain is
+//! restored, and the transactions of pruned blocks are removed from it.
+use super::{Query, TxEvent};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tendermint::abci::transaction::Hash;
+
+/// The position of a transaction, that is its height and index within the block.
+type Position = (u64, u32);
+
+/// The delivered transactions, ordered by height and index within the block.
+pub struct TxIndex {
+    txs: BTreeMap<Position, IndexedTx>,
+    by_hash: HashMap<Hash, Position>,
+    /// The positions of the transactions holding a composite key.
+    by_key: HashMap<String, BTreeSet<Position>>,
+}
+
+struct IndexedTx {
+    tx: TxEvent,
+    /// The composite keys of the transaction with their values, see `TxEvent::attributes`.
+    attributes: BTreeMap<String, Vec<String>>,
+}
+
+impl TxIndex {
+    pub fn new() -> Self {
+        TxIndex {
+            txs: BTreeMap::new(),
+            by_hash: HashMap::new(),
+            by_key: HashMap::new(),
+        }
+    }
+
+    /// Index a delivered transaction.
+    pub fn insert(&mut self, tx: TxEvent) {
+        let position = (tx.height, tx.index);
+        let attributes = tx.attributes();
+        for key in attributes.keys() {
+            self.by_key
+                .entry(key.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(position);
+        }
+        self.by_hash.insert(tx.hash, position);
+        self.txs.insert(position, IndexedTx { tx, attributes });
+    }
+
+    /// Remove the transactions of the block at `height`.
+    pub fn remove_height(&mut self, height: u64) {
+        let positions = self
+            .txs
+            .range((height, 0)..(height + 1, 0))
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        for position in positions {
+            let indexed = self.txs.remove(&position).unwrap();
+            // An identical transaction may have been delivered again at a later height
+            if self.by_hash.get(&indexed.tx.hash) == Some(&position) {
+                self.by_hash.remove(&indexed.tx.hash);
+            }
+            for key in indexed.attributes.keys() {
+                if let Some(positions) = self.by_key.get_mut(key) {
+                    positions.remove(&position);
+                    if positions.is_empty() {
+                        self.by_key.remove(key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return the transaction with the given hash.
+    pub fn get(&self, hash: &Hash) -> Option<&TxEvent> {
+        self.by_hash
+            .get(hash)
+            .and_then(|position| self.txs.get(position))
+            .map(|indexed| &indexed.tx)
+    }
+
+    /// Return the transactions matching `query`, ordered by height and index.
+    pub fn search(&self, query: &Query) -> Vec<&TxEvent> {
+        // A transaction must hold all the keys of the query
+        let mut candidates = Vec::new();
+        for key in query.keys() {
+            match self.by_key.get(key) {
+                Some(positions) => candidates.push(positions),
+                None => return vec![],
+            }
+        }
+        let positions = match candidates
+            .into_iter()
+            .min_by_key(|positions| positions.len())
+        {
+            Some(positions) => positions,
+            None => return vec![],
+        };
+        positions
+            .iter()
+            .map(|position| &self.txs[position])
+            .filter(|indexed| query.matches(&indexed.attributes))
+            .map(|indexed| &indexed.tx)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::tx_hash;
+    use tendermint::abci::tag::Tag;
+    use tendermint::abci::{Code, Event, Transaction};
+    use tendermint_rpc::endpoint::broadcast::tx_commit::TxResult;
+
+    fn tx(height: u64, index: u32, action: &str) -> TxEvent {
+        let tx = Transaction::new(format!("{}-{}", height, index).into_bytes());
+        TxEvent {
+            hash: tx_hash(&tx),
+            height,
+            index,
+            tx,
+            result: TxResult {
+                code: Code::Ok,
+                data: None,
+                log: "Success".into(),
+                codespace: Default::default(),
+                gas_used: 10.into(),
+                gas_wanted: 10.into(),
+                info: Default::default(),
+                events: vec![Event {
+                    type_str: String::from("message"),
+                    attributes: vec![Tag {
+                        key: "action".parse().unwrap(),
+                        value: action.parse().unwrap(),
+                    }],
+                }],
+            },
+        }
+    }
+
+    /// Return the positions of the transactions matching `query`.
+    fn search(index: &TxIndex, query: &str) -> Vec<Position> {
+        index
+            .search(&Query::parse(query).unwrap())
+            .iter()
+            .map(|tx| (tx.height, tx.index))
+            .collect()
+    }
+
+    #[test]
+    fn index() {
+        let mut index = TxIndex::new();
+        let txs = vec![
+            tx(2, 0, "create_client"),
+            tx(2, 1, "update_client"),
+            tx(3, 0, "update_client"),
+        ];
+        for tx in &txs {
+            index.insert(tx.clone());
+        }
+        assert_eq!(
+            search(&index, "message.action='update_client'"),
+            vec![(2, 1), (3, 0)]
+        );
+        assert_eq!(
+            search(&index, "message.action EXISTS AND tx.height>2"),
+            vec![(3, 0)]
+        );
+        assert!(search(&index, "send_packet.packet_sequence EXISTS").is_empty());
+        assert_eq!(index.get(&txs[1].hash).unwrap().index, 1);
+
+        // Pruned blocks are removed from the index
+        index.remove_height(2);
+        assert!(index.get(&txs[1].hash).is_none());
+        assert_eq!(search(&index, "message.action EXISTS"), vec![(3, 0)]);
+    }
+
+    #[test]
+    fn redelivered_tx() {
+        let mut index = TxIndex::new();
+        let first = tx(2, 0, "transfer");
+        let mut again = first.clone();
+        again.height = 4;
+        index.insert(first.clone());
+        index.insert(again);
+
+        // The hash points to the latest delivery, which outlives the pruned one
+        index.remove_height(2);
+        assert_eq!(index.get(&first.hash).unwrap().height, 4);
+        index.remove_height(4);
+        assert!(index.get(&first.hash).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+//! # Events
+//!
+//! The event bus of a chain: new blocks and delivered transactions are published on the bus, and
+//! forwarded to the WebSocket subscribers.
+//!
+//! As in Tendermint, each event exposes a map of composite keys (such as `tm.event` or
+//! `create_client.client_id`) to values, against which the subscription queries are matched (see
+//! the `query` module).
+mod ibc;
+mod index;
+mod query;
+
+pub use ibc::to_abci_events;
+pub use index::TxIndex;
+pub use query::Query;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tendermint::abci::transaction::Hash;
+use tendermint::abci::Transaction;
+use tendermint::Block as TMBlock;
+use tendermint_rpc::endpoint::broadcast::tx_commit::TxResult;
+use tokio::sync::broadcast;
+
+/// Number of events kept for slow subscribers, older events are dropped.
+const CAPACITY: usize = 256;
+
+/// An event, as defined by Tendermint.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A new block has been commited.
+    NewBlock(TMBlock),
+    /// A transaction has been delivered.
+    Tx(TxEvent),
+}
+
+/// A delivered transaction.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TxEvent {
+    pub hash: Hash,
+    pub height: u64,
+    /// The index of the transaction within its block.
+    pub index: u32,
+    pub tx: Transaction,
+    pub result: TxResult,
+}
+
+impl Event {
+    /// The type of the event, that is the value of the `tm.event` key.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Event::NewBlock(_) => "NewBlock",
+            Event::Tx(_) => "Tx",
+        }
+    }
+
+    /// Return the composite keys of the event with their values.
+    pub fn attributes(&self) -> BTreeMap<String, Vec<String>> {
+        match self {
+            Event::NewBlock(_) => {
+                let mut attributes = BTreeMap::new();
+                attributes.insert(
+                    String::from("tm.event"),
+                    vec![String::from(self.event_type())],
+                );
+                attributes
+            }
+            Event::Tx(tx) => tx.attributes(),
+        }
+    }
+}
+
+impl TxEvent {
+    /// Return the composite keys of the transaction event with their values.
+    pub fn attributes(&self) -> BTreeMap<String, Vec<String>> {
+        let mut attributes = BTreeMap::new();
+        attributes.insert(String::from("tm.event"), vec![String::from("Tx")]);
+        attributes.insert(String::from("tx.hash"), vec![self.hash.to_string()]);
+        attributes.insert(String::from("tx.height"), vec![self.height.to_string()]);
+        for event in &self.result.events {
+            for tag in &event.attributes {
+                attributes
+                    .entry(format!("{}.{}", event.type_str, tag.key))
+                    .or_insert_with(Vec::new)
+                    .push(tag.value.to_string());
+            }
+        }
+        attributes
+    }
+}
+
+/// Return the hash of a transaction, that is the SHA-256 of its bytes.
+pub fn tx_hash(tx: &Transaction) -> Hash {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&Sha256::digest(tx.as_bytes()));
+    Hash::new(hash)
+}
+
+/// A broadcast channel of events, publishing is a no-op if there is no subscriber.
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        EventBus { sender }
+    }
+
+    /// Subscribe to all future events.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Return true if there is at least one subscriber, events can then be skipped altogether.
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publish an event to all the subscribers.
+    pub fn publish(&self, event: Event) {
+        // An error only means that there is no subscriber
+        let _ = self.sender.send(event);
+    }
+}
@@ -0,0 +1,321 @@
+//! # Event queries
+//!
+//! A parser and evaluator for the Tendermint query language, used to filter the events sent to the
+//! subscribers, for instance:
+//!
+//! ```text
+//! tm.event='Tx' AND message.action='create_client' AND tx.height>5
+//! ```
+//!
+//! A query is a conjunction of conditions on the composite keys of an event, an event matches a
+//! condition if any of its values for that key does. The conditions and operands are the ones of
+//! `tendermint_rpc`, which builds the queries on the client side; this module only parses them
+//! back and evaluates them.
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::fmt;
+use tendermint_rpc::query::{Condition, Operand};
+
+/// A parsed query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    conditions: Vec<Condition>,
+}
+
+/// An error raised while parsing a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid query: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A key, keyword, number, date or time.
+    Word(String),
+    /// A single-quoted string.
+    Str(String),
+    Eq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl Query {
+    /// Parse a query, such as `tm.event='NewBlock'`.
+    pub fn parse(query: &str) -> Result<Self, ParseError> {
+        let mut tokens = tokenize(query)?.into_iter();
+        let mut conditions = Vec::new();
+        loop {
+            let key = match tokens.next() {
+                Some(Token::Word(key)) if !is_keyword(&key) => key,
+                other => return Err(unexpected(other, "a key")),
+            };
+            let condition = match tokens.next() {
+                Some(Token::Word(w)) if w == "EXISTS" => Condition::Exists(key),
+                Some(Token::Word(w)) if w == "CONTAINS" => match tokens.next() {
+                    Some(Token::Str(s)) => Condition::Contains(key, s),
+                    other => return Err(unexpected(other, "a string")),
+                },
+                Some(Token::Eq) => Condition::Eq(key, parse_operand(&mut tokens)?),
+                Some(Token::Lt) => Condition::Lt(key, parse_operand(&mut tokens)?),
+                Some(Token::Lte) => Condition::Lte(key, parse_operand(&mut tokens)?),
+                Some(Token::Gt) => Condition::Gt(key, parse_operand(&mut tokens)?),
+                Some(Token::Gte) => Condition::Gte(key, parse_operand(&mut tokens)?),
+                other => return Err(unexpected(other, "an operator")),
+            };
+            conditions.push(condition);
+            match tokens.next() {
+                None => break,
+                Some(Token::Word(w)) if w == "AND" => continue,
+                other => return Err(unexpected(other, "AND")),
+            }
+        }
+        Ok(Query { conditions })
+    }
+
+    /// Return the composite keys the query has conditions on.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.conditions.iter().map(key)
+    }
+
+    /// Return true if the event, given by its composite keys and their values, matches all the
+    /// conditions of the query.
+    pub fn matches(&self, attributes: &BTreeMap<String, Vec<String>>) -> bool {
+        self.conditions.iter().all(|condition| {
+            attributes.get(key(condition)).map_or(false, |values| {
+                values.iter().any(|value| matches(condition, value))
+            })
+        })
+    }
+}
+
+/// Return the composite key of a condition.
+fn key(condition: &Condition) -> &str {
+    match condition {
+        Condition::Eq(key, _)
+        | Condition::Lt(key, _)
+        | Condition::Lte(key, _)
+        | Condition::Gt(key, _)
+        | Condition::Gte(key, _)
+        | Condition::Contains(key, _)
+        | Condition::Exists(key) => key,
+    }
+}
+
+/// Return true if `value` satisfies the condition.
+fn matches(condition: &Condition, value: &str) -> bool {
+    use std::cmp::Ordering;
+    match condition {
+        Condition::Eq(_, operand) => compare(value, operand) == Some(Ordering::Equal),
+        Condition::Lt(_, operand) => compare(value, operand) == Some(Ordering::Less),
+        Condition::Lte(_, operand) => matches!(
+            compare(value, operand),
+            Some(Ordering::Less) | Some(Ordering::Equal)
+        ),
+        Condition::Gt(_, operand) => compare(value, operand) == Some(Ordering::Greater),
+        Condition::Gte(_, operand) => matches!(
+            compare(value, operand),
+            Some(Ordering::Greater) | Some(Ordering::Equal)
+        ),
+        Condition::Contains(_, s) => value.contains(s.as_str()),
+        Condition::Exists(_) => true,
+    }
+}
+
+/// Compare a value to an operand, return None if the value can not be interpreted as the type of
+/// the operand. Strings can only be tested for equality.
+fn compare(value: &str, operand: &Operand) -> Option<std::cmp::Ordering> {
+    match operand {
+        Operand::String(s) if value == s.as_str() => Some(std::cmp::Ordering::Equal),
+        Operand::String(_) => None,
+        Operand::Signed(n) => Some(value.parse::<i128>().ok()?.cmp(&(*n as i128))),
+        Operand::Unsigned(n) => Some(value.parse::<i128>().ok()?.cmp(&(*n as i128))),
+        Operand::Float(f) => value.parse::<f64>().ok()?.partial_cmp(f),
+        Operand::Date(date) => Some(parse_date(value)?.cmp(date)),
+        Operand::DateTime(time) => Some(parse_time(value)?.cmp(time)),
+    }
+}
+
+/// Parse the operand following a comparison operator.
+fn parse_operand<I: Iterator<Item = Token>>(tokens: &mut I) -> Result<Operand, ParseError> {
+    match tokens.next() {
+        Some(Token::Str(s)) => Ok(Operand::String(s)),
+        Some(Token::Word(w)) if w == "DATE" => match tokens.next() {
+            Some(Token::Word(date)) => parse_date(&date)
+                .map(Operand::Date)
+                .ok_or_else(|| ParseError(format!("invalid date '{}'", date))),
+            other => Err(unexpected(other, "a date")),
+        },
+        Some(Token::Word(w)) if w == "TIME" => match tokens.next() {
+            Some(Token::Word(time)) => parse_time(&time)
+                .map(Operand::DateTime)
+                .ok_or_else(|| ParseError(format!("invalid time '{}'", time))),
+            other => Err(unexpected(other, "a time")),
+        },
+        Some(Token::Word(number)) => {
+            parse_number(&number).ok_or_else(|| ParseError(format!("invalid number '{}'", number)))
+        }
+        other => Err(unexpected(other, "an operand")),
+    }
+}
+
+fn parse_number(number: &str) -> Option<Operand> {
+    if number.contains('.') {
+        number.parse().ok().map(Operand::Float)
+    } else if number.starts_with('-') {
+        number.parse().ok().map(Operand::Signed)
+    } else {
+        number.parse().ok().map(Operand::Unsigned)
+    }
+}
+
+/// Parse a date formatted as `YYYY-MM-DD`.
+fn parse_date(date: &str) -> Option<chrono::Date<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(Utc.from_utc_date(&date))
+}
+
+/// Parse a RFC 3339 time.
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    let time = DateTime::parse_from_rfc3339(time).ok()?;
+    Some(time.with_timezone(&Utc))
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "AND" | "EXISTS" | "CONTAINS" | "DATE" | "TIME")
+}
+
+fn unexpected(token: Option<Token>, expected: &str) -> ParseError {
+    match token {
+        Some(token) => ParseError(format!("expected {}, found {:?}", expected, token)),
+        None => ParseError(format!("expected {}, found end of query", expected)),
+    }
+}
+
+/// Split a query into tokens.
+fn tokenize(query: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '\'' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => s.push(c),
+                        None => return Err(ParseError(String::from("unterminated string"))),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Eq);
+            }
+            '<' | '>' => {
+                chars.next();
+                let or_equal = chars.peek() == Some(&'=');
+                if or_equal {
+                    chars.next();
+                }
+                tokens.push(match (c, or_equal) {
+                    ('<', false) => Token::Lt,
+                    ('<', true) => Token::Lte,
+                    ('>', false) => Token::Gt,
+                    _ => Token::Gte,
+                });
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "'=<>".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> BTreeMap<String, Vec<String>> {
+        let mut attributes = BTreeMap::new();
+        for (key, value) in pairs {
+            attributes
+                .entry(key.to_string())
+                .or_insert_with(Vec::new)
+                .push(value.to_string());
+        }
+        attributes
+    }
+
+    #[test]
+    fn parse() {
+        let query =
+            Query::parse("tm.event='Tx' AND message.action='create_client' AND tx.height>5")
+                .unwrap();
+        assert_eq!(query.conditions.len(), 3);
+        assert_eq!(
+            query.conditions[2],
+            Condition::Gt(String::from("tx.height"), Operand::Unsigned(5))
+        );
+        let query = Query::parse("a.b EXISTS AND c.d CONTAINS 'foo' AND e.f<=-1.5").unwrap();
+        assert_eq!(
+            query.conditions,
+            vec![
+                Condition::Exists(String::from("a.b")),
+                Condition::Contains(String::from("c.d"), String::from("foo")),
+                Condition::Lte(String::from("e.f"), Operand::Float(-1.5)),
+            ]
+        );
+        assert!(Query::parse("tx.time >= TIME 2013-05-03T14:45:00Z").is_ok());
+        assert!(Query::parse("tx.date = DATE 2013-05-03").is_ok());
+
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("tm.event='Tx' AND").is_err());
+        assert!(Query::parse("tm.event='Tx").is_err());
+        assert!(Query::parse("tm.event 'Tx'").is_err());
+        assert!(Query::parse("tx.height > five").is_err());
+        assert!(Query::parse("tm.event='Tx' OR tm.event='NewBlock'").is_err());
+    }
+
+    #[test]
+    fn matches() {
+        let event = attributes(&[
+            ("tm.event", "Tx"),
+            ("tx.height", "7"),
+            ("message.action", "create_client"),
+            ("message.action", "update_client"),
+        ]);
+        let matches = |query: &str| Query::parse(query).unwrap().matches(&event);
+        assert!(matches("tm.event='Tx'"));
+        assert!(matches(
+            "tm.event='Tx' AND message.action='create_client' AND tx.height>5"
+        ));
+        assert!(matches("message.action='update_client'"));
+        assert!(matches("tx.height>=7 AND tx.height<=7 AND tx.height<8.5"));
+        assert!(matches("message.action CONTAINS 'update'"));
+        assert!(matches("tx.height EXISTS"));
+        assert!(!matches("tm.event='NewBlock'"));
+        assert!(!matches("tm.event='Tx' AND tx.height>7"));
+        assert!(!matches("tx.hash EXISTS"));
+        assert!(!matches("message.action>5"));
+    }
+}
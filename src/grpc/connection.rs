@@ -0,0 +1,166 @@
+//! # gRPC Connection
+//!
+//! The IBC connection gRPC API.
+//!
+//! The responses do not include proofs yet, listing all the connections is not supported.
+use crate::logger::Log;
+use crate::node;
+use crate::store::Storage;
+use ibc::ics03_connection::connection::ConnectionEnd;
+use ibc::ics03_connection::context::ConnectionReader;
+use ibc::ics24_host::identifier::{ClientId, ConnectionId};
+use ibc_proto::ibc::core::client::v1::{Height, IdentifiedClientState};
+use ibc_proto::ibc::core::connection::v1;
+use ibc_proto::ibc::core::connection::v1::query_server::{Query, QueryServer};
+use std::str::FromStr;
+use tonic::{Request, Response, Status};
+
+pub fn get_service<S: 'static + Storage + Sync + Send>(
+    node: node::SharedNode<S>,
+    verbose: bool,
+) -> QueryServer<QueryService<S>> {
+    let query_service = QueryService::new(node, verbose);
+    QueryServer::new(query_service)
+}
+
+/// A struct handling the `Query` service.
+#[derive(Clone)]
+pub struct QueryService<S: Storage> {
+    node: node::SharedNode<S>,
+    verbose: bool,
+}
+
+impl<S: Storage> QueryService<S> {
+    fn new(node: node::SharedNode<S>, verbose: bool) -> Self {
+        QueryService { node, verbose }
+    }
+
+    /// Return the connection end with id `connection_id`.
+    fn connection_end(&self, connection_id: &str) -> Result<ConnectionEnd, Status> {
+        let connection_id = ConnectionId::from_str(connection_id)
+            .map_err(|_| Status::invalid_argument("Invalid connection id"))?;
+        self.node
+            .connection_end(&connection_id)
+            .ok_or_else(|| Status::not_found("Connection not found"))
+    }
+
+    /// Return the height of the state the responses are built from, that is the latest height.
+    fn proof_height(&self) -> Height {
+        let height = self.node.read().get_chain().get_height();
+        Height {
+            revision_number: height.revision_number,
+            revision_height: height.revision_height,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl<S: 'static + Storage + Sync + Send> Query for QueryService<S> {
+    async fn connection(
+        &self,
+        request: Request<v1::QueryConnectionRequest>,
+    ) -> Result<Response<v1::QueryConnectionResponse>, Status> {
+        if self.verbose {
+            log!(Log::GRPC, "/connection/connection {:?}", request);
+        }
+        let connection = self.connection_end(&request.get_ref().connection_id)?;
+        let response = v1::QueryConnectionResponse {
+            connection: Some(connection.into()),
+            proof: vec![],
+            proof_height: Some(self.proof_height()),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn connections(
+        &self,
+        request: Request<v1::QueryConnectionsRequest>,
+    ) -> Result<Response<v1::QueryConnectionsResponse>, Status> {
+        if self.verbose {
+            log!(Log::GRPC, "/connection/connections {:?}", request);
+        }
+        Err(Status::unimplemented(
+            "Listing connections is not supported",
+        ))
+    }
+
+    async fn client_connections(
+        &self,
+        request: Request<v1::QueryClientConnectionsRequest>,
+    ) -> Result<Response<v1::QueryClientConnectionsResponse>, Status> {
+        if self.verbose {
+            log!(Log::GRPC, "/connection/client_connections {:?}", request);
+        }
+        let client_id = ClientId::from_str(&request.get_ref().client_id)
+            .map_err(|_| Status::invalid_argument("Invalid client id"))?;
+        let connections = self
+            .node
+            .client_connections(&client_id)
+            .ok_or_else(|| Status::not_found("No connection for this client"))?;
+        let response = v1::QueryClientConnectionsResponse {
+            connection_paths: connections
+                .iter()
+                .map(|id| id.as_str().to_owned())
+                .collect(),
+            proof: vec![],
+            proof_height: Some(self.proof_height()),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn connection_client_state(
+        &self,
+        request: Request<v1::QueryConnectionClientStateRequest>,
+    ) -> Result<Response<v1::QueryConnectionClientStateResponse>, Status> {
+        if self.verbose {
+            log!(
+                Log::GRPC,
+                "/connection/connection_client_state {:?}",
+                request
+            );
+        }
+        let connection = self.connection_end(&request.get_ref().connection_id)?;
+        let client_id = connection.client_id();
+        let client_state = self
+            .node
+            .client_state(client_id)
+            .ok_or_else(|| Status::not_found("Client not found"))?;
+        let response = v1::QueryConnectionClientStateResponse {
+            identified_client_state: Some(IdentifiedClientState {
+                client_id: client_id.as_str().to_owned(),
+                client_state: Some(client_state.into()),
+            }),
+            proof: vec![],
+            proof_height: Some(self.proof_height()),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn connection_consensus_state(
+        &self,
+        request: Request<v1::QueryConnectionConsensusStateRequest>,
+    ) -> Result<Response<v1::QueryConnectionConsensusStateResponse>, Status> {
+        if self.verbose {
+            log!(
+                Log::GRPC,
+                "/connection/connection_consensus_state {:?}",
+                request
+            );
+        }
+        let request = request.get_ref();
+        let connection = self.connection_end(&request.connection_id)?;
+        let client_id = connection.client_id();
+        let height = ibc::Height::new(request.revision_number, request.revision_height);
+        let consensus_state = self
+            .node
+            .client_consensus_state(client_id, height)
+            .ok_or_else(|| Status::not_found("Consensus state not found"))?;
+        let response = v1::QueryConnectionConsensusStateResponse {
+            consensus_state: Some(consensus_state.into()),
+            client_id: client_id.as_str().to_owned(),
+            proof: vec![],
+            proof_height: Some(self.proof_height()),
+        };
+        Ok(Response::new(response))
+    }
+}
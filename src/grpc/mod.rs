@@ -9,30 +9,53 @@
 //! The server code is also generated, this time by [tonic](https://github.com/hyperium/tonic) and it also lives in the `ibc_proto`
 //! crate. This module simply implements the `Query` trait generated by `Tonic` on a custom
 //! `QueryService` struct.
-use crate::node;
 use crate::logger::Log;
+use crate::node;
 use crate::store::Storage;
 use futures::future::FutureExt;
+use std::future::Future;
+use std::net::SocketAddr;
 use tonic::transport::Server;
 
 mod auth;
+mod connection;
 mod staking;
 
-/// Create a new gRPC server.
-pub async fn serve<S: 'static + Storage + Sync + Send>(
+/// Bind a new gRPC server to `addr`, return the bound address and the server future.
+///
+/// If the port of `addr` is 0 a free port is picked by the OS, the returned address holds the
+/// actual port.
+///
+/// The server stops gracefully once `shutdown` resolves. This must be called from within a Tokio
+/// runtime.
+pub fn serve<S, F>(
     node: node::SharedNode<S>,
     verbose: bool,
-    addr: std::net::SocketAddr,
-) -> Result<(), std::convert::Infallible> {
-    Server::builder()
+    addr: SocketAddr,
+    shutdown: F,
+) -> std::io::Result<(SocketAddr, impl Future<Output = ()>)>
+where
+    S: 'static + Storage + Sync + Send,
+    F: Future<Output = ()> + Send + 'static,
+{
+    // Bind the listener right away, so that the server is reachable once this function returns
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let addr = listener.local_addr()?;
+    let incoming = futures::stream::unfold(listener, |listener| async move {
+        let stream = listener.accept().await.map(|(stream, _)| stream);
+        Some((stream, listener))
+    });
+    let server = Server::builder()
         .add_service(staking::get_service(node.clone(), verbose))
+        .add_service(connection::get_service(node.clone(), verbose))
         .add_service(auth::get_service(node, verbose))
-        .serve(addr)
-        .then(|result| async {
+        .serve_with_incoming_shutdown(Box::pin(incoming), shutdown)
+        .map(|result| {
             if let Err(e) = result {
                 log!(Log::GRPC, "Server error: {}", e);
             }
-            Ok(())
-        })
-        .await
+        });
+    Ok((addr, server))
 }
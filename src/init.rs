@@ -5,10 +5,12 @@
 //!
 //! The initial values are taken fron the configuration (see `config` module).
 use crate::config::{Client, Config};
-use ibc::ics02_client::client_def::AnyClientState;
+use ibc::ics02_client::client_def::{AnyClientState, AnyConsensusState};
 use ibc::ics02_client::client_type::ClientType;
-use ibc::ics02_client::context::ClientKeeper;
+use ibc::ics02_client::context::{ClientKeeper, ClientReader};
 use ibc::ics07_tendermint::client_state::ClientState;
+use ibc::ics07_tendermint::consensus_state::ConsensusState;
+use ibc::ics23_commitment::commitment::CommitmentRoot;
 use ibc::ics24_host::identifier::ClientId;
 use ibc::Height;
 use std::str::FromStr;
@@ -25,7 +27,8 @@ pub fn init<T: ClientKeeper>(keeper: &mut T, config: &Config) {
 fn add_client<T: ClientKeeper>(keeper: &mut T, client: &Client, config: &Config) {
     let client_id =
         ClientId::from_str(&client.id).expect(&format!("Invalid client id: {}", &client.id));
-    let client_state = new_client_state(config);
+    let height = Height::new(1, 1);
+    let client_state = new_client_state(&config.chain_id, height, height);
     keeper
         .store_client_state(client_id.clone(), client_state)
         .unwrap();
@@ -34,16 +37,47 @@ fn add_client<T: ClientKeeper>(keeper: &mut T, client: &Client, config: &Config)
         .unwrap();
 }
 
-fn new_client_state(config: &Config) -> AnyClientState {
+/// Create a client of a counterparty chain, whose consensus state is built from the
+/// counterparty's `header` at `height`. Return the id of the new client.
+pub fn add_counterparty_client<T: ClientKeeper + ClientReader>(
+    keeper: &mut T,
+    chain_id: &str,
+    height: Height,
+    header: &tendermint::block::Header,
+) -> ClientId {
+    let client_type = ClientType::Tendermint;
+    let client_id = format!("{}-{}", client_type.as_string(), keeper.client_counter());
+    let client_id = ClientId::from_str(&client_id).expect("[Internal] Invalid client id");
+    // A zero frozen height means that the client is not frozen
+    let client_state = new_client_state(chain_id, height, Height::new(0, 0));
+    let consensus_state = ConsensusState {
+        timestamp: header.time,
+        next_validators_hash: header.next_validators_hash,
+        root: CommitmentRoot::from_bytes(header.app_hash.as_ref()),
+    };
+    keeper
+        .store_client_state(client_id.clone(), client_state)
+        .unwrap();
+    keeper
+        .store_consensus_state(
+            client_id.clone(),
+            height,
+            AnyConsensusState::Tendermint(consensus_state),
+        )
+        .unwrap();
+    keeper.increase_client_counter();
+    client_id
+}
+
+fn new_client_state(chain_id: &str, height: Height, frozen_height: Height) -> AnyClientState {
     let duration = std::time::Duration::new(3600 * 24 * 30, 0);
-    let height = Height::new(1, 1);
     let client_state = ClientState {
-        chain_id: String::from(&config.chain_id),
+        chain_id: String::from(chain_id),
         trusting_period: duration.clone(),
         trust_level: TrustThresholdFraction::new(1, 3).unwrap(),
         unbonding_period: duration.clone(),
         max_clock_drift: duration,
-        frozen_height: height.clone(),
+        frozen_height,
         latest_height: height,
         upgrade_path: vec![String::from("path")],
         allow_update_after_expiry: false,
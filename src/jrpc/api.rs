@@ -2,8 +2,8 @@
 use ibc::ics26_routing::handler::deliver;
 use ibc_proto::cosmos::tx::v1beta1::{TxBody, TxRaw};
 use prost::Message;
-use tendermint::abci::{transaction::Hash, Code, Info};
 use tendermint::abci::responses::Codespace;
+use tendermint::abci::{Code, Info};
 use tendermint_rpc::endpoint::{
     abci_info::Request as AbciInfoRequest, abci_info::Response as AbciInfoResponse,
     abci_query::Request as AbciQueryRequest, abci_query::Response as AbciQueryResponse,
@@ -17,16 +17,13 @@ use tendermint_rpc::endpoint::{
 };
 
 use crate::abci;
-use crate::chain::to_full_block;
+use crate::events::to_abci_events;
 use crate::logger::Log;
 use crate::node;
 use crate::store;
 
 use super::utils::{JrpcError, JrpcFilter, JrpcResult};
 
-const PUBLICK_KEY: &str = "4A25C6640A1F72B9C975338294EF51B6D1C33158BB6ECBA69FBC3FB5A33C9DCE";
-const HASH_LENGHT: usize = 32; // tendermint::abci::transaction::hash::LENGHT is not exposed...
-
 /// A structure to build the JsonRPC HTTP API, see the `new` method.
 pub struct Jrpc<S: store::Storage>
 where
@@ -66,6 +63,15 @@ where
             .add("abci_info", Self::abci_info)
             .add("abci_query", Self::abci_query)
             .add("broadcast_tx_commit", Self::broadcast_tx_commit)
+            .add("tx", Self::tx)
+            .add("tx_search", Self::tx_search)
+            .add("tendermock_fork", Self::fork)
+            .add("tendermock_grow", Self::grow)
+            .add("tendermock_grow_n", Self::grow_n)
+            .add("tendermock_pause", Self::pause)
+            .add("tendermock_resume", Self::resume)
+            .add("tendermock_set_time", Self::set_time)
+            .add("tendermock_advance_time", Self::advance_time)
             .build()
     }
 
@@ -79,11 +85,10 @@ where
             Some(height) => height.into(),
         };
         let node = state.node.read();
-        let block = node
+        let tm_block = node
             .get_chain()
-            .get_block(height)
-            .ok_or_else(|| JrpcError::InvalidRequest)?;
-        let tm_block = to_full_block(block);
+            .get_full_block(height)
+            .ok_or_else(|| Self::missing_block(&node, height))?;
         let hash = tm_block.header.hash();
         Ok(BlockResponse {
             block_id: tendermint::block::Id {
@@ -107,7 +112,7 @@ where
         let block = node
             .get_chain()
             .get_block(height)
-            .ok_or_else(|| JrpcError::InvalidRequest)?;
+            .ok_or_else(|| Self::missing_block(&node, height))?;
         let signed_header = block.signed_header;
         Ok(CommitResponse {
             signed_header,
@@ -121,7 +126,10 @@ where
             log!(Log::JRPC, "/genesis    {:?}", req);
         }
         let node = state.node.read();
-        let genesis_block = node.get_chain().get_block(1).unwrap();
+        let genesis_block = node
+            .get_chain()
+            .get_block(1)
+            .ok_or(JrpcError::ServerError)?;
         let genesis = tendermint::Genesis {
             genesis_time: genesis_block.signed_header.header.time,
             chain_id: node.get_chain_id().clone(),
@@ -139,10 +147,11 @@ where
             log!(Log::JRPC, "/validators {:?}", req);
         }
         let node = state.node.read();
+        let height = req.height.into();
         let block = node
             .get_chain()
-            .get_block(req.height.into())
-            .ok_or_else(|| JrpcError::InvalidRequest)?;
+            .get_block(height)
+            .ok_or_else(|| Self::missing_block(&node, height))?;
         let validators = block.validators.validators().clone();
         Ok(ValidatorResponse {
             block_height: block.signed_header.header.height,
@@ -157,16 +166,8 @@ where
         }
         let node = state.node.read();
         let node_info = node.get_info().clone();
-        let sync_info = node.get_sync_info();
-        let validator_info = tendermint::validator::Info {
-            address: tendermint::account::Id::new([41; 20]),
-            pub_key: tendermint::public_key::PublicKey::from_raw_ed25519(
-                &hex::decode(PUBLICK_KEY).unwrap(),
-            )
-            .unwrap(),
-            voting_power: (1 as u32).into(),
-            proposer_priority: 1.into(),
-        };
+        let sync_info = node.get_sync_info().ok_or(JrpcError::ServerError)?;
+        let validator_info = node.get_validator_info().ok_or(JrpcError::ServerError)?;
         Ok(StatusResponse {
             node_info,
             sync_info,
@@ -207,19 +208,22 @@ where
         mut state: Self,
     ) -> JrpcResult<BroadcastTxCommitResponse> {
         if state.verbose {
-            log!(Log::JRPC, "/broadcast_tx_commit {{ tx: {} bytes }}", req.tx.as_bytes().len());
+            log!(
+                Log::JRPC,
+                "/broadcast_tx_commit {{ tx: {} bytes }}",
+                req.tx.as_bytes().len()
+            );
         }
-        // Grow chain
-        let node = state.node.write();
-        node.get_chain().grow();
-        let block = node.get_chain().get_block(0).unwrap();
-        drop(node); // Release write lock
-
         // Build transactions
+        let tx = req.tx.clone();
         let data: Vec<u8> = req.tx.into();
         let tx_raw = TxRaw::decode(&*data).map_err(|_| JrpcError::InvalidRequest)?;
         let tx_body = TxBody::decode(&*tx_raw.body_bytes).map_err(|_| JrpcError::InvalidRequest)?;
-        deliver(&mut state.node, tx_body.messages).map_err(|e| {
+        // No other block can be commited until the transaction is, so that its writes, events and
+        // height all refer to the same block.
+        let shared = state.node.clone();
+        let _commits = shared.lock_commits();
+        let events = deliver(&mut state.node, tx_body.messages).map_err(|e| {
             log!(Log::JRPC, "deliver error: '{}'", e);
             JrpcError::ServerError
         })?;
@@ -233,13 +237,28 @@ where
             gas_used: 10.into(),
             gas_wanted: 10.into(),
             info: Info::default(),
-            events: vec![],
+            events: to_abci_events(&events),
         };
+
+        // Include the transaction in the pending block, then commit it
+        let node = state.node.write();
+        let tx = node.get_chain().deliver_tx(tx, tx_result.clone());
+        node.get_chain().grow();
+        drop(node); // Release write lock
         Ok(BroadcastTxCommitResponse {
             check_tx: tx_result.clone(),
             deliver_tx: tx_result,
-            hash: Hash::new([61; HASH_LENGHT]),
-            height: block.signed_header.header.height,
+            hash: tx.hash,
+            height: (tx.height as u32).into(),
         })
     }
+
+    /// Return the error matching a missing block, which may have been pruned.
+    pub(super) fn missing_block(node: &node::Node<S>, height: u64) -> JrpcError {
+        if node.get_chain().is_pruned(height) {
+            JrpcError::PrunedHeight(height)
+        } else {
+            JrpcError::InvalidRequest
+        }
+    }
 }
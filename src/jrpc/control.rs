@@ -0,0 +1,163 @@
+//! The Tendermock JsonRPC control API.
+//!
+//! Those methods are not part of the Tendermint API, they are used to control the mocked chain
+//! from integration tests and are prefixed with `tendermock_`.
+use serde::{Deserialize, Serialize};
+use tendermint::block;
+use tendermint_rpc::endpoint::commit::Response as CommitResponse;
+
+use crate::chain::Fork;
+use crate::logger::Log;
+use crate::node;
+use crate::store;
+
+use super::api::Jrpc;
+use super::utils::{JrpcError, JrpcResult};
+
+/// Maximum number of blocks produced by a single `tendermock_grow_n` call.
+const MAX_GROW_N: u64 = 1000;
+
+/// Parameters of the methods without arguments.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct EmptyRequest {}
+
+/// Parameters of the `tendermock_grow_n` method, `n` is at most `MAX_GROW_N`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GrowNRequest {
+    pub n: u64,
+}
+
+/// Response of the `tendermock_grow` and `tendermock_grow_n` methods.
+#[derive(Serialize, Debug)]
+pub struct GrowResponse {
+    /// The height of the chain after growth.
+    pub height: block::Height,
+}
+
+/// Response of the `tendermock_pause` and `tendermock_resume` methods.
+#[derive(Serialize, Debug)]
+pub struct PauseResponse {
+    pub paused: bool,
+}
+
+/// Parameters of the `tendermock_set_time` method.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SetTimeRequest {
+    /// Seconds since the unix epoch.
+    pub time: u64,
+}
+
+/// Parameters of the `tendermock_advance_time` method.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AdvanceTimeRequest {
+    pub seconds: u64,
+}
+
+/// Response of the `tendermock_set_time` and `tendermock_advance_time` methods.
+#[derive(Serialize, Debug)]
+pub struct TimeResponse {
+    /// Time of the next block, in seconds since the unix epoch.
+    pub time: u64,
+}
+
+/// Parameters of the `tendermock_fork` method.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ForkRequest {
+    pub height: block::Height,
+    #[serde(default)]
+    pub kind: Fork,
+}
+
+impl<S> Jrpc<S>
+where
+    S: 'static + store::Storage,
+    node::SharedNode<S>: Sync + Send + Clone,
+{
+    /// JsonRPC tendermock_fork endpoint, returns a signed header conflicting with the canonical
+    /// one at the requested height.
+    pub(super) fn fork(req: ForkRequest, state: Self) -> JrpcResult<CommitResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_fork {:?}", req);
+        }
+        let height = req.height.into();
+        let node = state.node.read();
+        let block = node
+            .get_chain()
+            .fork(height, req.kind)
+            .ok_or_else(|| Self::missing_block(&node, height))?;
+        Ok(CommitResponse {
+            signed_header: block.signed_header,
+            canonical: false,
+        })
+    }
+
+    /// JsonRPC tendermock_grow endpoint, adds a single block to the chain.
+    pub(super) fn grow(req: EmptyRequest, state: Self) -> JrpcResult<GrowResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_grow {:?}", req);
+        }
+        state.node.grow();
+        Ok(Self::grow_response(&state))
+    }
+
+    /// JsonRPC tendermock_grow_n endpoint, adds `n` blocks to the chain.
+    pub(super) fn grow_n(req: GrowNRequest, state: Self) -> JrpcResult<GrowResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_grow_n {:?}", req);
+        }
+        if req.n > MAX_GROW_N {
+            return Err(JrpcError::WrongParameters);
+        }
+        state.node.grow_n(req.n);
+        Ok(Self::grow_response(&state))
+    }
+
+    /// JsonRPC tendermock_pause endpoint, pauses the automatic block production.
+    pub(super) fn pause(req: EmptyRequest, state: Self) -> JrpcResult<PauseResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_pause {:?}", req);
+        }
+        state.node.pause();
+        Ok(PauseResponse { paused: true })
+    }
+
+    /// JsonRPC tendermock_resume endpoint, resumes the automatic block production.
+    pub(super) fn resume(req: EmptyRequest, state: Self) -> JrpcResult<PauseResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_resume {:?}", req);
+        }
+        state.node.resume();
+        Ok(PauseResponse { paused: false })
+    }
+
+    /// JsonRPC tendermock_set_time endpoint, sets the time of the next block.
+    pub(super) fn set_time(req: SetTimeRequest, state: Self) -> JrpcResult<TimeResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_set_time {:?}", req);
+        }
+        let time = state.node.read().get_chain().set_time(req.time);
+        Ok(TimeResponse { time })
+    }
+
+    /// JsonRPC tendermock_advance_time endpoint, moves the time of the next block forward.
+    pub(super) fn advance_time(req: AdvanceTimeRequest, state: Self) -> JrpcResult<TimeResponse> {
+        if state.verbose {
+            log!(Log::JRPC, "/tendermock_advance_time {:?}", req);
+        }
+        let time = state.node.read().get_chain().advance_time(req.seconds);
+        Ok(TimeResponse { time })
+    }
+
+    /// Build a response holding the current height of the chain.
+    fn grow_response(state: &Self) -> GrowResponse {
+        let height = state.node.read().get_chain().get_height().revision_height;
+        GrowResponse {
+            height: block::Height::from(height as u32),
+        }
+    }
+}
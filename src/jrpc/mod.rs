@@ -6,6 +6,8 @@
 //! `warp` is a HTTP framework which is built around filters, this module is used to build filters
 //! that mimics the Tendermint API.
 mod api;
+mod control;
+mod tx;
 mod utils;
 mod websockets;
 
@@ -14,22 +16,30 @@ pub use websockets::Ws;
 
 use crate::node;
 use crate::store::Storage;
-use futures::future::FutureExt;
+use std::future::Future;
+use std::net::SocketAddr;
 use warp;
 use warp::Filter as _;
 
 pub const WEBSOCKET_PATH: &str = "websocket";
 
-/// Create a new gRPC server.
-pub async fn serve<S: 'static + Storage + Sync + Send>(
+/// Bind a new JsonRPC server to `addr`, return the bound address and the server future.
+///
+/// If the port of `addr` is 0 a free port is picked by the OS, the returned address holds the
+/// actual port.
+///
+/// The server stops gracefully once `shutdown` resolves.
+pub fn serve<S, F>(
     node: node::SharedNode<S>,
     verbose: bool,
-    addr: std::net::SocketAddr,
-) -> Result<(), std::convert::Infallible> {
+    addr: SocketAddr,
+    shutdown: F,
+) -> Result<(SocketAddr, impl Future<Output = ()>), warp::Error>
+where
+    S: 'static + Storage + Sync + Send,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let ws = warp::path(WEBSOCKET_PATH).and(Ws::new(node.clone()));
     let jrpc_api = warp::path::end().and(Jrpc::new(verbose, node));
-    let ws = warp::path(WEBSOCKET_PATH).and(Ws::new());
-    warp::serve(jrpc_api.or(ws))
-        .run(addr)
-        .then(|()| async { Ok(()) })
-        .await
+    warp::serve(jrpc_api.or(ws)).try_bind_with_graceful_shutdown(addr, shutdown)
 }
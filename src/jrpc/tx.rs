@@ -0,0 +1,190 @@
+//! The Tendermock JsonRPC transaction API, that is the `/tx` and `/tx_search` endpoints.
+//!
+//! The request types follow the Tendermint JSON encoding: hashes may either be given in hex or
+//! base64, and integers may be given as strings. The responses are the ones of `tendermint_rpc`.
+//!
+//! Transaction proofs are not supported, requests with `prove=true` are rejected.
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use tendermint::abci::responses::DeliverTx;
+use tendermint::abci::transaction::Hash;
+use tendermint::block;
+use tendermint_rpc::endpoint::{tx, tx_search};
+
+use crate::events::{Query, TxEvent};
+use crate::logger::Log;
+use crate::node;
+use crate::store;
+
+use super::api::Jrpc;
+use super::utils::{JrpcError, JrpcResult};
+
+/// Number of transactions per page, if not specified.
+const DEFAULT_PER_PAGE: u64 = 30;
+/// Maximum number of transactions per page.
+const MAX_PER_PAGE: u64 = 100;
+
+/// Parameters of the `tx` method.
+#[derive(Deserialize, Debug)]
+pub struct TxRequest {
+    pub hash: String,
+    #[serde(default)]
+    pub prove: bool,
+}
+
+/// Parameters of the `tx_search` method.
+#[derive(Deserialize, Debug)]
+pub struct TxSearchRequest {
+    pub query: String,
+    #[serde(default)]
+    pub prove: bool,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub page: Option<u64>,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub per_page: Option<u64>,
+    #[serde(default)]
+    pub order_by: Order,
+}
+
+/// The order of the transactions returned by `tx_search`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Order::Asc
+    }
+}
+
+impl<S> Jrpc<S>
+where
+    S: 'static + store::Storage,
+    node::SharedNode<S>: Sync + Send + Clone,
+{
+    /// JsonRPC /tx endpoint, returns a delivered transaction by hash.
+    pub(super) fn tx(req: TxRequest, state: Self) -> JrpcResult<tx::Response> {
+        if state.verbose {
+            log!(Log::JRPC, "/tx         {:?}", req);
+        }
+        check_no_proof(req.prove)?;
+        let hash = parse_hash(&req.hash).ok_or(JrpcError::WrongParameters)?;
+        let tx = state
+            .node
+            .read()
+            .get_chain()
+            .get_tx(&hash)
+            .ok_or(JrpcError::InvalidRequest)?;
+        let tx = to_result_tx(tx);
+        Ok(tx::Response {
+            hash: tx.hash,
+            height: tx.height,
+            index: tx.index,
+            tx_result: tx.tx_result,
+            tx: tx.tx,
+            proof: None,
+        })
+    }
+
+    /// JsonRPC /tx_search endpoint, returns the delivered transactions matching a query.
+    pub(super) fn tx_search(req: TxSearchRequest, state: Self) -> JrpcResult<tx_search::Response> {
+        if state.verbose {
+            log!(Log::JRPC, "/tx_search  {:?}", req);
+        }
+        check_no_proof(req.prove)?;
+        let query = Query::parse(&req.query).map_err(|e| {
+            log!(Log::JRPC, "tx_search error: '{}'", e);
+            JrpcError::WrongParameters
+        })?;
+        let mut txs = state.node.read().get_chain().search_txs(&query);
+        if req.order_by == Order::Desc {
+            txs.reverse();
+        }
+
+        // Paginate, as Tendermint the page must exist unless there is no result at all
+        let total_count = txs.len() as u64;
+        let per_page = match req.per_page {
+            Some(per_page) if per_page > 0 => std::cmp::min(per_page, MAX_PER_PAGE),
+            _ => DEFAULT_PER_PAGE,
+        };
+        let pages = std::cmp::max(1, (total_count + per_page - 1) / per_page);
+        let page = req.page.unwrap_or(1);
+        if page < 1 || page > pages {
+            return Err(JrpcError::WrongParameters);
+        }
+        let txs = txs
+            .into_iter()
+            .skip(((page - 1) * per_page) as usize)
+            .take(per_page as usize)
+            .map(to_result_tx)
+            .collect();
+        Ok(tx_search::Response {
+            txs,
+            total_count: total_count as u32,
+        })
+    }
+}
+
+/// Return an error if a proof is requested, as transaction proofs are not supported.
+fn check_no_proof(prove: bool) -> JrpcResult<()> {
+    if prove {
+        log!(Log::JRPC, "Error: transaction proofs are not supported");
+        return Err(JrpcError::WrongParameters);
+    }
+    Ok(())
+}
+
+/// Convert a delivered transaction into its RPC representation, without proof.
+fn to_result_tx(tx: TxEvent) -> tx_search::ResultTx {
+    let result = tx.result;
+    tx_search::ResultTx {
+        hash: tx.hash,
+        height: block::Height::from(tx.height as u32),
+        index: tx.index,
+        tx_result: DeliverTx {
+            code: result.code,
+            data: result.data.unwrap_or_default(),
+            log: result.log,
+            info: result.info,
+            gas_wanted: result.gas_wanted,
+            gas_used: result.gas_used,
+            events: result.events,
+            codespace: result.codespace,
+        },
+        tx: tx.tx,
+        proof: None,
+    }
+}
+
+/// Parse a transaction hash, either in hex (as returned by tendermock) or in base64 (as expected
+/// by Tendermint).
+fn parse_hash(hash: &str) -> Option<Hash> {
+    if let Ok(hash) = hash.trim_start_matches("0x").parse::<Hash>() {
+        return Some(hash);
+    }
+    let bytes = base64::decode(hash).ok()?;
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&bytes);
+    Some(Hash::new(hash))
+}
+
+/// Deserialize an optional integer, which may be encoded as a string.
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+    match Option::<NumberOrString>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(n)) => Ok(Some(n)),
+        Some(NumberOrString::String(s)) => s.parse().map(Some).map_err(de::Error::custom),
+    }
+}
@@ -42,6 +42,7 @@ pub enum JrpcError {
     WrongVersion,
     ServerError,
     InvalidRequest,
+    PrunedHeight(u64),
 }
 
 /// JsonRPC error details.
@@ -198,6 +199,10 @@ impl From<JrpcError> for JrpcErrorDetails {
                 code: JRPC_CODE_INVALID_REQUEST,
                 message: "Invalid request".to_string(),
             },
+            JrpcError::PrunedHeight(height) => JrpcErrorDetails {
+                code: JRPC_CODE_INVALID_PARAMS,
+                message: format!("Height {} has been pruned", height),
+            },
         }
     }
 }
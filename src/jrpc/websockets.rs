@@ -1,48 +1,101 @@
 //! The Tendermock JsonRPC Websocket API.
+//!
+//! Each connection holds its own subscriptions, the events published on the chain's event bus are
+//! forwarded to the connection for each subscription whose query matches the event.
 use futures::{SinkExt, StreamExt};
 use serde::Serialize;
 use serde_json;
+use std::collections::BTreeMap;
+use tendermint::abci::Transaction;
+use tendermint::Block as TMBlock;
+use tendermint_rpc::endpoint::broadcast::tx_commit::TxResult;
 use tendermint_rpc::endpoint::subscribe::{Request, Response};
+use tendermint_rpc::endpoint::unsubscribe::{
+    Request as UnsubscribeRequest, Response as UnsubscribeResponse,
+};
+use tokio::sync::broadcast;
 use warp::ws::{Message, WebSocket, Ws as WarpWs};
 use warp::Filter;
 
 use super::utils::{JrpcEnvelope, JrpcError, JrpcResponse, JrpcResult, JRPC_VERSION};
+use crate::events::{Event, Query};
 use crate::logger::Log;
+use crate::node;
+use crate::store::Storage;
 
 /// A struct that can be used to build the Websocket `warp` filter, see the `new` method.
 pub struct Ws {}
 
 impl Ws {
     /// Creates a `warp` filter that mimics the Tendermint Websocket API.
-    pub fn new() -> warp::filters::BoxedFilter<(impl warp::Reply,)> {
+    pub fn new<S>(node: node::SharedNode<S>) -> warp::filters::BoxedFilter<(impl warp::Reply,)>
+    where
+        S: 'static + Storage + Sync + Send,
+    {
         warp::ws()
-            .map(|ws: WarpWs| ws.on_upgrade(move |socket| handler(socket)))
+            .map(move |ws: WarpWs| {
+                let events = node.read().get_chain().get_events().subscribe();
+                ws.on_upgrade(move |socket| handler(socket, events))
+            })
             .boxed()
     }
 }
 
+/// A subscription of a websocket connection, notifications are sent with the id of the subscribe
+/// request.
+struct Subscription {
+    id: String,
+    /// The query, as sent by the subscriber.
+    query: String,
+    parsed_query: Query,
+}
+
 /// Handle a websocket connection.
-async fn handler(ws: WebSocket) {
+async fn handler(ws: WebSocket, mut events: broadcast::Receiver<Event>) {
     log!(Log::Websocket, "Connection");
     let (mut sending_ws, mut listening_ws) = ws.split();
-    while let Some(result) = listening_ws.next().await {
-        let msg = match result {
-            Ok(msg) => msg,
-            Err(e) => {
-                log!(Log::Websocket, "Receiving error: '{}'", e);
-                break;
+    let mut subscriptions = Vec::new();
+    loop {
+        tokio::select! {
+            result = listening_ws.next() => {
+                let msg = match result {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(e)) => {
+                        log!(Log::Websocket, "Receiving error: '{}'", e);
+                        break;
+                    }
+                    None => break,
+                };
+                if msg.is_close() {
+                    break;
+                }
+                let msg = if let Ok(msg) = msg.to_str() {
+                    msg
+                } else {
+                    // Pings are answered by warp
+                    continue;
+                };
+                let response = handle_request(msg, &mut subscriptions);
+                if let Err(e) = sending_ws.send(Message::text(response)).await {
+                    log!(Log::Websocket, "Sending error: '{}'", e);
+                    break;
+                };
             }
-        };
-        let msg = if let Ok(msg) = msg.to_str() {
-            msg
-        } else {
-            log!(Log::Websocket, "Could not interpret message as str");
-            break;
-        };
-        if let Err(e) = sending_ws.send(Message::text(handle_request(msg))).await {
-            log!(Log::Websocket, "Sending error: '{}'", e);
-            break;
-        };
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        log!(Log::Websocket, "Dropped {} events", n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Err(e) = notify(&mut sending_ws, &subscriptions, &event).await {
+                    log!(Log::Websocket, "Sending error: '{}'", e);
+                    break;
+                }
+            }
+        }
     }
     if let Ok(ws) = sending_ws.reunite(listening_ws) {
         if let Err(e) = ws.close().await {
@@ -51,9 +104,42 @@ async fn handler(ws: WebSocket) {
     };
 }
 
+/// Send a notification for each subscription matching the event.
+async fn notify<W>(
+    sending_ws: &mut W,
+    subscriptions: &[Subscription],
+    event: &Event,
+) -> Result<(), warp::Error>
+where
+    W: futures::Sink<Message, Error = warp::Error> + Unpin,
+{
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+    let attributes = event.attributes();
+    for subscription in subscriptions {
+        if !subscription.parsed_query.matches(&attributes) {
+            continue;
+        }
+        let notification = JrpcResponse {
+            id: subscription.id.clone(),
+            jsonrpc: JRPC_VERSION.to_string(),
+            result: Some(EventNotification {
+                query: &subscription.query,
+                data: EventData::from(event),
+                events: &attributes,
+            }),
+            error: None,
+        };
+        let notification = serde_json::to_string(&notification).unwrap();
+        sending_ws.send(Message::text(notification)).await?;
+    }
+    Ok(())
+}
+
 /// Parses the request, dispatch the query to the method handler and return the serialized Jrpc
 /// response.
-fn handle_request(msg: &str) -> String {
+fn handle_request(msg: &str, subscriptions: &mut Vec<Subscription>) -> String {
     let req = if let Ok(req) = parse_message(msg) {
         req
     } else {
@@ -70,9 +156,17 @@ fn handle_request(msg: &str) -> String {
     }
     match req.method.as_str() {
         "subscribe" => {
-            let res = subscribe(&req);
+            let res = subscribe(&req, subscriptions);
+            serialize_response(res, req)
+        }
+        "unsubscribe" => {
+            let res = unsubscribe(&req, subscriptions);
             serialize_response(res, req)
         }
+        "unsubscribe_all" => {
+            subscriptions.clear();
+            serialize_response(Ok(UnsubscribeResponse {}), req)
+        }
         _ => serialize_response::<()>(Err(JrpcError::WrongMethod), req),
     }
 }
@@ -98,8 +192,89 @@ fn serialize_response<T: Serialize>(result: JrpcResult<T>, envelope: JrpcEnvelop
 }
 
 /// Handles the /subscribe endpoint.
-fn subscribe(req: &JrpcEnvelope) -> JrpcResult<Response> {
-    let _params: Request =
+fn subscribe(req: &JrpcEnvelope, subscriptions: &mut Vec<Subscription>) -> JrpcResult<Response> {
+    let params: Request =
         serde_json::from_value(req.params.clone()).map_err(|_| JrpcError::WrongParameters)?;
+    if subscriptions.iter().any(|s| s.query == params.query) {
+        return Err(JrpcError::InvalidRequest);
+    }
+    let parsed_query = Query::parse(&params.query).map_err(|e| {
+        log!(Log::Websocket, "Subscription error: '{}'", e);
+        JrpcError::WrongParameters
+    })?;
+    subscriptions.push(Subscription {
+        id: req.id.clone(),
+        query: params.query,
+        parsed_query,
+    });
     Ok(Response {})
 }
+
+/// Handles the /unsubscribe endpoint.
+fn unsubscribe(
+    req: &JrpcEnvelope,
+    subscriptions: &mut Vec<Subscription>,
+) -> JrpcResult<UnsubscribeResponse> {
+    let params: UnsubscribeRequest =
+        serde_json::from_value(req.params.clone()).map_err(|_| JrpcError::WrongParameters)?;
+    let count = subscriptions.len();
+    subscriptions.retain(|s| s.query != params.query);
+    if subscriptions.len() == count {
+        return Err(JrpcError::InvalidRequest);
+    }
+    Ok(UnsubscribeResponse {})
+}
+
+/// The result of an event notification, as sent by Tendermint.
+#[derive(Serialize)]
+struct EventNotification<'a> {
+    query: &'a str,
+    data: EventData<'a>,
+    events: &'a BTreeMap<String, Vec<String>>,
+}
+
+/// The data of an event notification, tagged with the Tendermint (amino) type.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "value")]
+enum EventData<'a> {
+    #[serde(rename = "tendermint/event/NewBlock")]
+    NewBlock {
+        block: &'a TMBlock,
+        result_begin_block: serde_json::Value,
+        result_end_block: serde_json::Value,
+    },
+    #[serde(rename = "tendermint/event/Tx")]
+    Tx {
+        #[serde(rename = "TxResult")]
+        tx_result: TxInfo<'a>,
+    },
+}
+
+/// A delivered transaction, as sent by Tendermint.
+#[derive(Serialize)]
+struct TxInfo<'a> {
+    height: String,
+    index: u32,
+    tx: &'a Transaction,
+    result: &'a TxResult,
+}
+
+impl<'a> From<&'a Event> for EventData<'a> {
+    fn from(event: &'a Event) -> Self {
+        match event {
+            Event::NewBlock(block) => EventData::NewBlock {
+                block,
+                result_begin_block: serde_json::json!({}),
+                result_end_block: serde_json::json!({ "validator_updates": null }),
+            },
+            Event::Tx(tx) => EventData::Tx {
+                tx_result: TxInfo {
+                    height: tx.height.to_string(),
+                    index: tx.index,
+                    tx: &tx.tx,
+                    result: &tx.result,
+                },
+            },
+        }
+    }
+}
@@ -14,6 +14,22 @@
 //!     .add_interface(jrpc_addr, grpc_addr)
 //!     .start();
 //! ```
+//!
+//! The `start` method is blocking, `spawn` (or `run` from an async context) returns a handle once
+//! the servers are bound:
+//!
+//! ```no_run
+//! use tendermock::Tendermock;
+//!
+//! let jrpc_addr = format!("127.0.0.1:{}", 5000).parse().unwrap();
+//! let grpc_addr = format!("127.0.0.1:{}", 6000).parse().unwrap();
+//!
+//! let handle = Tendermock::new()
+//!     .add_interface(jrpc_addr, grpc_addr)
+//!     .spawn();
+//! // Query the node...
+//! handle.shutdown();
+//! ```
 
 #[macro_use]
 mod logger;
@@ -21,13 +37,21 @@ mod logger;
 mod abci;
 mod avl;
 mod builder;
+mod capabilities;
 mod chain;
 mod config;
+mod events;
 mod grpc;
 mod init;
 mod jrpc;
 mod node;
+mod packets;
 mod store;
 mod test_node;
 
-pub use builder::Tendermock;
+pub use builder::{Control, Network, NetworkHandle, Tendermock, TendermockHandle};
+pub use chain::Clock;
+pub use config::ConfigError;
+pub use node::SharedNode;
+pub use packets::{PacketKeeper, PacketReader};
+pub use store::{DynStorage, Pruning};
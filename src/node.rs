@@ -6,9 +6,10 @@
 //!
 //! To integrate with IBC modules, the node implements the `ICS26Context` traits, which mainly deal
 //! with storing and reading values from the store.
-#![allow(unused_variables)] // lot of todos...
-use crate::chain::Chain;
+use crate::capabilities::Capabilities;
+use crate::chain::{Chain, ValidatorChanges};
 use crate::config::Config;
+use crate::packets::{PacketKeeper, PacketReader};
 use crate::store::{InMemoryStore, Storage};
 use ibc::ics02_client::client_def::{AnyClientState, AnyConsensusState};
 use ibc::ics02_client::client_type::ClientType;
@@ -27,10 +28,10 @@ use ibc::ics23_commitment::commitment::CommitmentPrefix;
 use ibc::ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId};
 use ibc::ics26_routing::context::ICS26Context;
 use ibc::Height;
-use ibc_proto::ibc::core::connection::v1::ConnectionEnd as RawConnectionEnd;
+use ibc_proto::ibc::core::channel::v1::Channel as RawChannel;
+use ibc_proto::ibc::core::connection::v1::{ClientPaths, ConnectionEnd as RawConnectionEnd};
 use prost::Message;
 use prost_types::Any;
-use serde::{Deserialize, Serialize};
 use serde_json;
 use std::convert::TryFrom;
 use std::str::FromStr;
@@ -39,19 +40,28 @@ use tendermint::net::Address;
 use tendermint::node;
 use tendermint_proto::Protobuf;
 use tendermint_rpc::endpoint::status::SyncInfo;
+use tendermint_testgen::{Generator, Validator};
 
 // System constant
 const COMMITMENT_PREFIX: &'static str = "store/ibc/key";
 
+// Identifier counters, stored under the same keys as the Cosmos SDK.
+const CLIENT_COUNTER: &'static str = "nextClientSequence";
+const CONNECTION_COUNTER: &'static str = "nextConnectionSequence";
+const CHANNEL_COUNTER: &'static str = "nextChannelSequence";
+
 /// An `Arc<RwLock<>>` wrapper around a Node.
 pub struct SharedNode<S: Storage> {
     node: std::sync::Arc<std::sync::RwLock<Node<S>>>,
+    /// Held while commiting blocks, see `lock_commits`.
+    commits: std::sync::Arc<std::sync::Mutex<()>>,
 }
 
 impl<S: Storage> Clone for SharedNode<S> {
     fn clone(&self) -> Self {
         Self {
             node: std::sync::Arc::clone(&self.node),
+            commits: std::sync::Arc::clone(&self.commits),
         }
     }
 }
@@ -67,11 +77,63 @@ impl<S: Storage> SharedNode<S> {
         self.node.write().unwrap()
     }
 
+    /// Prevent other blocks from being commited until the guard is dropped, the guard holder can
+    /// still commit blocks with `write().grow()`.
+    ///
+    /// The IBC handlers acquire the node lock for each read or write, this allows to deliver and
+    /// commit a transaction without a concurrent growth splitting its writes across blocks.
+    pub fn lock_commits(&self) -> std::sync::MutexGuard<()> {
+        self.commits.lock().unwrap()
+    }
+
     /// Grow the chain.
-    #[allow(dead_code)]
     pub fn grow(&self) {
+        let _commits = self.lock_commits();
         self.node.write().unwrap().grow();
     }
+
+    /// Grow the chain by `n` consecutive blocks.
+    ///
+    /// The node is only locked while producing each block, so that other requests are served in
+    /// between.
+    pub fn grow_n(&self, n: u64) {
+        let _commits = self.lock_commits();
+        for _ in 0..n {
+            self.write().grow();
+        }
+    }
+
+    /// Pause the automatic block production, blocks can still be produced manually.
+    pub fn pause(&self) {
+        self.write().paused = true;
+    }
+
+    /// Resume the automatic block production.
+    pub fn resume(&self) {
+        self.write().paused = false;
+    }
+
+    /// Return true if the automatic block production is paused.
+    pub fn is_paused(&self) -> bool {
+        self.read().paused
+    }
+
+    /// Schedule a change of the validator set at `height`: the validator is either added,
+    /// updated or removed if `voting_power` is 0.
+    ///
+    /// Returns false if `height` is not after the pending block, whose successor's validators
+    /// are already known.
+    pub fn set_validator(&self, height: u64, id: &str, voting_power: u64) -> bool {
+        let validator = Validator::new(id).voting_power(voting_power);
+        self.read()
+            .chain
+            .schedule_validator_change(height, validator)
+    }
+
+    /// Bind a port at runtime, return its capability or None if the port is already bound.
+    pub fn bind_port(&self, port_id: &PortId) -> Option<Capability> {
+        self.write().capabilities.bind_port(port_id)
+    }
 }
 
 /// A node contains a store, a chain and some meta-data.
@@ -81,10 +143,22 @@ pub struct Node<S: Storage> {
     host_client_id: String,
     info: node::Info,
     consensus_params: tendermint::consensus::Params,
+    capabilities: Capabilities,
+    /// The validator run by this node.
+    validator: Validator,
+    /// Whether the automatic block production is paused.
+    paused: bool,
 }
 
 impl Node<InMemoryStore> {
     pub fn new(config: &Config) -> Self {
+        Node::with_store(config, InMemoryStore::with_pruning(config.pruning.into()))
+    }
+}
+
+impl<S: Storage> Node<S> {
+    /// Create a node on top of a given store.
+    pub fn with_store(config: &Config, store: S) -> Self {
         // TODO: allow to pass custimized values
         let info = node::Info {
             // Node id
@@ -102,28 +176,59 @@ impl Node<InMemoryStore> {
                 .unwrap(),
             moniker: tendermint::Moniker::from_str("moniker").unwrap(),
             other: node::info::OtherInfo {
-                tx_index: node::info::TxIndexStatus::Off,
+                tx_index: node::info::TxIndexStatus::On,
                 rpc_address: Address::from_str("tcp://127.0.0.1:26657").unwrap(),
             },
         };
+        let mut capabilities = Capabilities::new();
+        // Ports are validated when loading the configuration
+        for port in &config.ports {
+            let port_id = PortId::from_str(port).expect("[Internal] Invalid port id");
+            capabilities.bind_port(&port_id);
+        }
+        let validators = config
+            .validators
+            .iter()
+            .map(|v| Validator::new(&v.id).voting_power(v.voting_power))
+            .collect::<Vec<Validator>>();
+        // Validators are validated when loading the configuration
+        let validator = validators
+            .first()
+            .expect("[Internal] At least one validator is required")
+            .clone();
+        let mut validator_changes = ValidatorChanges::new();
+        for change in &config.validator_changes {
+            validator_changes
+                .entry(change.height)
+                .or_insert_with(Vec::new)
+                .push(Validator::new(&change.id).voting_power(change.voting_power));
+        }
         Node {
-            chain: Chain::new(InMemoryStore::new()),
+            chain: Chain::new(
+                store,
+                &config.chain_id,
+                &validators,
+                validator_changes,
+                config.clock.into(),
+            ),
             chain_id: tendermint::chain::Id::try_from(config.chain_id.to_owned()).unwrap(),
             host_client_id: config.host_client.id.to_owned(),
             consensus_params: config.consensus_params.clone(),
+            capabilities,
+            validator,
+            paused: false,
             info,
         }
     }
 
     /// Return the node in an Arc<RwLock> wrapper, ready to be shared among threads.
-    pub fn shared(self) -> SharedNode<InMemoryStore> {
+    pub fn shared(self) -> SharedNode<S> {
         SharedNode {
             node: std::sync::Arc::new(std::sync::RwLock::new(self)),
+            commits: std::sync::Arc::new(std::sync::Mutex::new(())),
         }
     }
-}
 
-impl<S: Storage> Node<S> {
     pub fn get_store(&self) -> &S {
         &self.chain.get_store()
     }
@@ -148,21 +253,52 @@ impl<S: Storage> Node<S> {
         self.chain.grow();
     }
 
-    /// Get sync infos. For now only the field `latest_block_height` contains a valid value.
-    pub fn get_sync_info(&self) -> SyncInfo {
+    /// Return the informations of the validator run by this node, that is the first validator
+    /// of the configuration, with its voting power at the latest height.
+    ///
+    /// Returns None if the latest block can not be built.
+    pub fn get_validator_info(&self) -> Option<tendermint::validator::Info> {
+        let mut info = self.validator.generate().ok()?;
+        let block = self.chain.get_block(0)?;
+        info.voting_power = block
+            .validators
+            .validators()
+            .iter()
+            .find(|v| v.address == info.address)
+            .map(|v| v.voting_power)
+            .unwrap_or_else(|| (0 as u32).into());
+        Some(info)
+    }
+
+    /// Set the address advertised in the node infos, that is the address of the JsonRPC server.
+    pub fn set_listen_addr(&mut self, addr: std::net::SocketAddr) {
+        self.info.listen_addr = node::info::ListenAddress::new(addr.to_string());
+        self.info.other.rpc_address = Address::from_str(&format!("tcp://{}", addr))
+            .expect("[Internal] Invalid listen address");
+    }
+
+    /// Get sync infos, or None if the latest block can not be built.
+    pub fn get_sync_info(&self) -> Option<SyncInfo> {
         let latest_block_height = self.chain.get_height();
-        let block = self
-            .chain
-            .get_block(0)
-            .expect("The chain should always contain a block");
+        let block = self.chain.get_block(0)?;
         let hash = block.signed_header.header.hash();
-        SyncInfo {
+        Some(SyncInfo {
             latest_block_hash: hash,
-            latest_app_hash: tendermint::AppHash::try_from(vec![61 as u8; 32]).unwrap(),
+            latest_app_hash: tendermint::AppHash::try_from(
+                self.chain.get_app_hash(latest_block_height.revision_height),
+            )
+            .ok()?,
             latest_block_height: (latest_block_height.revision_height as u32).into(),
             latest_block_time: block.signed_header.header.time,
             catching_up: false,
-        }
+        })
+    }
+
+    /// Return the value at `path` in the pending block, which includes writes that are not yet
+    /// commited.
+    fn get_pending(&self, path: &[u8]) -> Option<Vec<u8>> {
+        let height = self.chain.get_height().revision_height + 1;
+        self.get_store().get(height, path)
     }
 }
 
@@ -202,7 +338,7 @@ impl<S: Storage> ClientReader for SharedNode<S> {
     }
 
     fn client_counter(&self) -> u64 {
-        todo!();
+        self.get_counter(CLIENT_COUNTER)
     }
 }
 
@@ -263,7 +399,7 @@ impl<S: Storage> ClientKeeper for SharedNode<S> {
     }
 
     fn increase_client_counter(&mut self) {
-        todo!();
+        self.increase_counter(CLIENT_COUNTER);
     }
 }
 
@@ -289,20 +425,21 @@ impl<S: Storage> ConnectionKeeper for SharedNode<S> {
     ) -> Result<(), ConnectionError> {
         let path = format!("clients/{}/connections", client_id.as_str());
         let node = self.read();
-        let store = node.get_store();
-        let connections = store.get(0, path.as_bytes()).unwrap_or(vec![]);
-        let connections = String::from_utf8(connections).unwrap_or(String::from(""));
-        let mut connections =
-            serde_json::from_str::<Connections>(&connections).unwrap_or(Connections::new());
-        connections
-            .connections
-            .push(connection_id.as_str().to_owned());
-        store.set(path.into_bytes(), connection_id.as_bytes().to_owned());
+        let mut connections = node
+            .get_pending(path.as_bytes())
+            .and_then(|value| ClientPaths::decode(&*value).ok())
+            .unwrap_or_default();
+        connections.paths.push(connection_id.as_str().to_owned());
+        let mut buffer = Vec::new();
+        connections.encode(&mut buffer).unwrap();
+        node.get_store().set(path.into_bytes(), buffer);
         Ok(())
     }
 
     fn next_connection_id(&mut self) -> ConnectionId {
-        todo!();
+        let counter = self.increase_counter(CONNECTION_COUNTER);
+        ConnectionId::from_str(&format!("connection-{}", counter))
+            .expect("[Internal] Invalid connection identifier")
     }
 }
 
@@ -325,7 +462,9 @@ impl<S: Storage> ConnectionReader for SharedNode<S> {
     }
 
     fn host_chain_history_size(&self) -> usize {
-        100
+        let node = self.read();
+        let store = node.get_store();
+        store.pruning().history_size(store.height()) as usize
     }
 
     fn commitment_prefix(&self) -> CommitmentPrefix {
@@ -363,9 +502,27 @@ impl<S: Storage> ConnectionReader for SharedNode<S> {
     }
 }
 
+impl<S: Storage> SharedNode<S> {
+    /// Return the list of connections built on top of a given client.
+    pub fn client_connections(&self, client_id: &ClientId) -> Option<Vec<ConnectionId>> {
+        let path = format!("clients/{}/connections", client_id.as_str());
+        let node = self.read();
+        let store = node.get_store();
+        let value = store.get(0, path.as_bytes())?;
+        let connections = ClientPaths::decode(&*value).ok()?;
+        connections
+            .paths
+            .iter()
+            .map(|id| ConnectionId::from_str(id).ok())
+            .collect()
+    }
+}
+
 impl<S: Storage> ChannelKeeper for SharedNode<S> {
     fn next_channel_id(&mut self) -> ChannelId {
-        todo!();
+        let counter = self.increase_counter(CHANNEL_COUNTER);
+        ChannelId::from_str(&format!("channel-{}", counter))
+            .expect("[Internal] Invalid channel identifier")
     }
 
     fn store_connection_channels(
@@ -373,7 +530,23 @@ impl<S: Storage> ChannelKeeper for SharedNode<S> {
         conn_id: &ConnectionId,
         port_channel_id: &(PortId, ChannelId),
     ) -> Result<(), ChannelError> {
-        todo!();
+        // Stored as the client connections, that is a list of ICS24 paths
+        let path = format!("connections/{}/channels", conn_id.as_str());
+        let node = self.read();
+        let mut channels = node
+            .get_pending(path.as_bytes())
+            .and_then(|value| ClientPaths::decode(&*value).ok())
+            .unwrap_or_default();
+        let (port_id, channel_id) = port_channel_id;
+        channels.paths.push(format!(
+            "ports/{}/channels/{}",
+            port_id.as_str(),
+            channel_id.as_str()
+        ));
+        let mut buffer = Vec::new();
+        channels.encode(&mut buffer).unwrap();
+        node.get_store().set(path.into_bytes(), buffer);
+        Ok(())
     }
 
     fn store_channel(
@@ -381,7 +554,18 @@ impl<S: Storage> ChannelKeeper for SharedNode<S> {
         port_channel_id: &(PortId, ChannelId),
         channel_end: &ChannelEnd,
     ) -> Result<(), ChannelError> {
-        todo!();
+        let mut buffer = Vec::new();
+        let (port_id, channel_id) = port_channel_id;
+        let path = format!(
+            "channelEnds/ports/{}/channels/{}",
+            port_id.as_str(),
+            channel_id.as_str()
+        );
+        let raw: RawChannel = channel_end.to_owned().into();
+        raw.encode(&mut buffer).unwrap();
+        let node = self.write();
+        node.get_store().set(path.into_bytes(), buffer);
+        Ok(())
     }
 
     fn store_next_sequence_send(
@@ -389,7 +573,8 @@ impl<S: Storage> ChannelKeeper for SharedNode<S> {
         port_channel_id: &(PortId, ChannelId),
         seq: u64,
     ) -> Result<(), ChannelError> {
-        todo!();
+        self.store_sequence("seqSends", port_channel_id, seq);
+        Ok(())
     }
 
     fn store_next_sequence_recv(
@@ -397,7 +582,8 @@ impl<S: Storage> ChannelKeeper for SharedNode<S> {
         port_channel_id: &(PortId, ChannelId),
         seq: u64,
     ) -> Result<(), ChannelError> {
-        todo!();
+        self.store_sequence("seqRecvs", port_channel_id, seq);
+        Ok(())
     }
 
     fn store_next_sequence_ack(
@@ -405,28 +591,49 @@ impl<S: Storage> ChannelKeeper for SharedNode<S> {
         port_channel_id: &(PortId, ChannelId),
         seq: u64,
     ) -> Result<(), ChannelError> {
-        todo!();
+        self.store_sequence("seqAcks", port_channel_id, seq);
+        Ok(())
     }
 }
 
 impl<S: Storage> ChannelReader for SharedNode<S> {
     fn channel_end(&self, port_channel_id: &(PortId, ChannelId)) -> Option<ChannelEnd> {
-        todo!();
+        let (port_id, channel_id) = port_channel_id;
+        let path = format!(
+            "channelEnds/ports/{}/channels/{}",
+            port_id.as_str(),
+            channel_id.as_str()
+        );
+        let node = self.read();
+        let store = node.get_store();
+        let value = store.get(0, path.as_bytes())?;
+        let raw = RawChannel::decode(&*value).ok()?;
+        ChannelEnd::try_from(raw).ok()
     }
 
     fn connection_end(&self, connection_id: &ConnectionId) -> Option<ConnectionEnd> {
-        todo!();
+        <SharedNode<S> as ConnectionReader>::connection_end(self, connection_id)
     }
 
     fn connection_channels(&self, cid: &ConnectionId) -> Option<Vec<(PortId, ChannelId)>> {
-        todo!();
+        let path = format!("connections/{}/channels", cid.as_str());
+        let node = self.read();
+        let store = node.get_store();
+        let value = store.get(0, path.as_bytes())?;
+        let channels = ClientPaths::decode(&*value).ok()?;
+        channels
+            .paths
+            .iter()
+            .map(|path| parse_channel_path(path))
+            .collect()
     }
 
     fn channel_client_state(
         &self,
         port_channel_id: &(PortId, ChannelId),
     ) -> Option<AnyClientState> {
-        todo!();
+        let client_id = self.channel_client_id(port_channel_id)?;
+        <SharedNode<S> as ClientReader>::client_state(self, &client_id)
     }
 
     fn channel_client_consensus_state(
@@ -434,40 +641,182 @@ impl<S: Storage> ChannelReader for SharedNode<S> {
         port_channel_id: &(PortId, ChannelId),
         height: Height,
     ) -> Option<AnyConsensusState> {
-        todo!();
+        let client_id = self.channel_client_id(port_channel_id)?;
+        self.consensus_state(&client_id, height)
     }
 
     fn port_capability(&self, port_id: &PortId) -> Option<Capability> {
-        todo!();
+        self.read().capabilities.lookup_port(port_id)
     }
 
     fn capability_authentification(&self, port_id: &PortId, cap: &Capability) -> bool {
-        todo!();
+        self.read().capabilities.authenticate(port_id, cap)
+    }
+}
+
+impl<S: Storage> SharedNode<S> {
+    /// Return the current value of a counter, including pending increments.
+    fn get_counter(&self, key: &str) -> u64 {
+        let node = self.read();
+        match node.get_pending(key.as_bytes()) {
+            Some(value) if value.len() == 8 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&value);
+                u64::from_be_bytes(bytes)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Increase a counter by one and return its previous value.
+    fn increase_counter(&self, key: &str) -> u64 {
+        let counter = self.get_counter(key);
+        let node = self.read();
+        node.get_store().set(
+            key.as_bytes().to_owned(),
+            (counter + 1).to_be_bytes().to_vec(),
+        );
+        counter
+    }
+
+    /// Store a sequence number, `kind` is one of `seqSends`, `seqRecvs` or `seqAcks`.
+    fn store_sequence(&self, kind: &str, port_channel_id: &(PortId, ChannelId), seq: u64) {
+        let (port_id, channel_id) = port_channel_id;
+        let path = format!(
+            "{}/ports/{}/channels/{}",
+            kind,
+            port_id.as_str(),
+            channel_id.as_str()
+        );
+        let node = self.read();
+        node.get_store()
+            .set(path.into_bytes(), seq.to_be_bytes().to_vec());
+    }
+
+    /// Return the ID of the client on top of which a channel is built, i.e. the client of the
+    /// first connection hop.
+    fn channel_client_id(&self, port_channel_id: &(PortId, ChannelId)) -> Option<ClientId> {
+        let channel = self.channel_end(port_channel_id)?;
+        let connection_id = channel.connection_hops().get(0)?;
+        let connection = <SharedNode<S> as ConnectionReader>::connection_end(self, connection_id)?;
+        Some(connection.client_id().to_owned())
+    }
+}
+
+/// Packet commitments, receipts and acknowledgements, stored under their ICS24 paths.
+impl<S: Storage> PacketKeeper for SharedNode<S> {
+    fn store_packet_commitment(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+        commitment: Vec<u8>,
+    ) -> Result<(), ChannelError> {
+        let path = packet_path("commitments", port_channel_id, seq);
+        self.read().get_store().set(path.into_bytes(), commitment);
+        Ok(())
+    }
+
+    fn delete_packet_commitment(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError> {
+        let path = packet_path("commitments", port_channel_id, seq);
+        self.read().get_store().delete(path.as_bytes());
+        Ok(())
+    }
+
+    fn store_packet_receipt(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError> {
+        let path = packet_path("receipts", port_channel_id, seq);
+        // Same as the Cosmos SDK, receipts are a single byte
+        self.read().get_store().set(path.into_bytes(), vec![1]);
+        Ok(())
+    }
+
+    fn delete_packet_receipt(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError> {
+        let path = packet_path("receipts", port_channel_id, seq);
+        self.read().get_store().delete(path.as_bytes());
+        Ok(())
+    }
+
+    fn store_packet_acknowledgement(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+        ack_commitment: Vec<u8>,
+    ) -> Result<(), ChannelError> {
+        let path = packet_path("acks", port_channel_id, seq);
+        self.read()
+            .get_store()
+            .set(path.into_bytes(), ack_commitment);
+        Ok(())
+    }
+}
+
+impl<S: Storage> PacketReader for SharedNode<S> {
+    fn packet_commitment(
+        &self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Option<Vec<u8>> {
+        let path = packet_path("commitments", port_channel_id, seq);
+        self.read().get_store().get(0, path.as_bytes())
+    }
+
+    fn packet_receipt(&self, port_channel_id: &(PortId, ChannelId), seq: u64) -> bool {
+        let path = packet_path("receipts", port_channel_id, seq);
+        self.read().get_store().get(0, path.as_bytes()).is_some()
+    }
+
+    fn packet_acknowledgement(
+        &self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Option<Vec<u8>> {
+        let path = packet_path("acks", port_channel_id, seq);
+        self.read().get_store().get(0, path.as_bytes())
     }
 }
 
 impl<S: Storage> PortReader for SharedNode<S> {
     fn lookup_module_by_port(&self, port_id: &PortId) -> Option<Capability> {
-        todo!();
+        self.read().capabilities.lookup_port(port_id)
     }
 
     fn autenthenticate(&self, key: &Capability, port_id: &PortId) -> bool {
-        todo!();
+        self.read().capabilities.authenticate(port_id, key)
     }
 }
 
 impl<S: Storage> ICS26Context for SharedNode<S> {}
 
-/// A type representing connections in memory
-#[derive(Serialize, Deserialize)]
-struct Connections {
-    pub connections: Vec<String>,
+/// Build the ICS24 path of a packet, `kind` is one of `commitments`, `receipts` or `acks`.
+fn packet_path(kind: &str, port_channel_id: &(PortId, ChannelId), seq: u64) -> String {
+    let (port_id, channel_id) = port_channel_id;
+    format!(
+        "{}/ports/{}/channels/{}/sequences/{}",
+        kind,
+        port_id.as_str(),
+        channel_id.as_str(),
+        seq
+    )
 }
 
-impl Connections {
-    pub fn new() -> Self {
-        Connections {
-            connections: Vec::new(),
-        }
+/// Parse a channel path, formatted as `ports/{port_id}/channels/{channel_id}`.
+fn parse_channel_path(path: &str) -> Option<(PortId, ChannelId)> {
+    match path.split('/').collect::<Vec<_>>().as_slice() {
+        ["ports", port_id, "channels", channel_id] => Some((
+            PortId::from_str(port_id).ok()?,
+            ChannelId::from_str(channel_id).ok()?,
+        )),
+        _ => None,
     }
 }
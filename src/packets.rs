@@ -0,0 +1,60 @@
+//! # Packets
+//!
+//! The version of the `ibc` crate we rely on does not define packet keepers yet, those traits
+//! mirror the `ChannelKeeper` and `ChannelReader` interfaces for packet commitments, receipts and
+//! acknowledgements. They are implemented by the node, alongside the other ICS26 contexts, so that
+//! packet handlers can be written against them.
+use ibc::ics04_channel::error::Error as ChannelError;
+use ibc::ics24_host::identifier::{ChannelId, PortId};
+
+/// Read access to the packet data of a channel.
+pub trait PacketReader {
+    /// Return the commitment of the packet sent on a channel with sequence `seq`.
+    fn packet_commitment(&self, port_channel_id: &(PortId, ChannelId), seq: u64)
+        -> Option<Vec<u8>>;
+
+    /// Return true if the packet with sequence `seq` has been received on a channel.
+    fn packet_receipt(&self, port_channel_id: &(PortId, ChannelId), seq: u64) -> bool;
+
+    /// Return the acknowledgement commitment of the packet received with sequence `seq`.
+    fn packet_acknowledgement(
+        &self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Option<Vec<u8>>;
+}
+
+/// Write access to the packet data of a channel.
+pub trait PacketKeeper {
+    fn store_packet_commitment(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+        commitment: Vec<u8>,
+    ) -> Result<(), ChannelError>;
+
+    fn delete_packet_commitment(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError>;
+
+    fn store_packet_receipt(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError>;
+
+    fn delete_packet_receipt(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+    ) -> Result<(), ChannelError>;
+
+    fn store_packet_acknowledgement(
+        &mut self,
+        port_channel_id: &(PortId, ChannelId),
+        seq: u64,
+        ack_commitment: Vec<u8>,
+    ) -> Result<(), ChannelError>;
+}
@@ -0,0 +1,327 @@
+//! # File Store
+//!
+//! A persistent storage, backed by an append-only log of per-height diffs.
+//!
+//! Each line of the log is a JSON object holding the operations (set or delete) applied during a
+//! block. When the store is opened, the log is replayed on top of an `InMemoryStore`, which then
+//! serves all the queries, including those targeting historical heights. The data attached to
+//! each block (see `Storage::set_block_data`) is persisted alongside its operations, so that the
+//! blocks themselves can be restored. As a consequence restarting tendermock preserves both the
+//! state and the heights of the chain.
+//!
+//! The log is never pruned, only the in-memory replica is. A log with missing or out-of-order
+//! heights, or with invalid hex data, is rejected.
+//!
+//! If a diff can not be written, the error is logged and the following blocks are no longer
+//! persisted: the node keeps serving from memory, and a restart restores the chain up to the last
+//! persisted block.
+use super::{InMemoryStore, Pruning, Storage};
+use crate::logger::Log;
+use ics23::CommitmentProof;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+/// An operation on the store, keys and values are hex-encoded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+enum Operation {
+    Set(String, String),
+    Delete(String),
+}
+
+/// The operations applied during a block.
+#[derive(Serialize, Deserialize)]
+struct Diff {
+    height: u64,
+    operations: Vec<Operation>,
+    /// The data attached to the block, hex-encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    block: Option<String>,
+}
+
+/// A persistent store, see module documentation.
+pub struct FileStore {
+    /// An in-memory replica of the store.
+    memory: InMemoryStore,
+    /// Operations applied on the pending block, not yet persisted.
+    pending: Mutex<Vec<Operation>>,
+    /// Data attached to the pending block, not yet persisted.
+    pending_block: Mutex<Option<Vec<u8>>>,
+    /// Data attached to the commited blocks, indexed by height - 1.
+    blocks: RwLock<Vec<Option<Vec<u8>>>>,
+    /// The log, or None if persisting a diff failed.
+    log: Mutex<Option<File>>,
+}
+
+impl FileStore {
+    /// Open a file store, the file is created if it does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P, pruning: Pruning) -> std::io::Result<Self> {
+        let memory = InMemoryStore::with_pruning(pruning);
+        let log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        // Replay the log, the genesis block has no data attached.
+        let mut blocks = vec![None];
+        let mut valid_len = 0;
+        let mut reader = BufReader::new(&log);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            let diff = match serde_json::from_str::<Diff>(&line) {
+                Ok(diff) if line.ends_with('\n') => diff,
+                // The last line may be truncated if tendermock was interrupted while writing
+                _ if reader.fill_buf()?.is_empty() => break,
+                _ => return Err(invalid_data("Corrupted store log".to_owned())),
+            };
+            let expected = memory.height() + 1;
+            if diff.height != expected {
+                return Err(invalid_data(format!(
+                    "Unexpected height {} in store log, expected {}",
+                    diff.height, expected
+                )));
+            }
+            for operation in diff.operations {
+                apply(&memory, &operation)?;
+            }
+            let block = match diff.block.map(hex::decode).transpose() {
+                Ok(block) => block,
+                Err(_) => return Err(invalid_data("Invalid block data in store log".to_owned())),
+            };
+            blocks.push(block);
+            memory.grow();
+            prune(&memory, &mut blocks);
+            valid_len += read as u64;
+        }
+        // Drop the truncated line, if any, so that new diffs are appended after a complete one.
+        log.set_len(valid_len)?;
+        Ok(FileStore {
+            memory,
+            pending: Mutex::new(Vec::new()),
+            pending_block: Mutex::new(None),
+            blocks: RwLock::new(blocks),
+            log: Mutex::new(Some(log)),
+        })
+    }
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Drop the data attached to the blocks pruned by the last growth of the store.
+fn prune(store: &InMemoryStore, blocks: &mut Vec<Option<Vec<u8>>>) {
+    if let Some(height) = store.pruning().pruned_at(store.height()) {
+        blocks[(height - 1) as usize] = None;
+    }
+}
+
+/// Apply an operation to the pending block of an in-memory store.
+fn apply(store: &InMemoryStore, operation: &Operation) -> std::io::Result<()> {
+    match operation {
+        Operation::Set(path, value) => store.set(decode(path)?, decode(value)?),
+        Operation::Delete(path) => store.delete(&decode(path)?),
+    }
+    Ok(())
+}
+
+fn decode(hex: &str) -> std::io::Result<Vec<u8>> {
+    hex::decode(hex).map_err(|_| invalid_data(format!("Invalid hex in store log: {}", hex)))
+}
+
+impl std::fmt::Debug for FileStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FileStore {{ memory: {:?} }}", self.memory)
+    }
+}
+
+impl Storage for FileStore {
+    fn set(&self, path: Vec<u8>, value: Vec<u8>) {
+        let operation = Operation::Set(hex::encode(&path), hex::encode(&value));
+        self.pending.lock().unwrap().push(operation);
+        self.memory.set(path, value);
+    }
+
+    fn delete(&self, path: &[u8]) {
+        let operation = Operation::Delete(hex::encode(path));
+        self.pending.lock().unwrap().push(operation);
+        self.memory.delete(path);
+    }
+
+    fn get(&self, height: u64, path: &[u8]) -> Option<Vec<u8>> {
+        self.memory.get(height, path)
+    }
+
+    fn get_proof(&self, height: u64, path: &[u8]) -> Option<CommitmentProof> {
+        self.memory.get_proof(height, path)
+    }
+
+    fn root_hash(&self, height: u64) -> Option<Vec<u8>> {
+        self.memory.root_hash(height)
+    }
+
+    fn height(&self) -> u64 {
+        self.memory.height()
+    }
+
+    fn is_pruned(&self, height: u64) -> bool {
+        self.memory.is_pruned(height)
+    }
+
+    fn pruning(&self) -> Pruning {
+        self.memory.pruning()
+    }
+
+    fn grow(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        let block = self.pending_block.lock().unwrap().take();
+        let diff = Diff {
+            height: self.memory.height() + 1,
+            operations: std::mem::take(&mut *pending),
+            block: block.as_ref().map(hex::encode),
+        };
+        let mut line = serde_json::to_string(&diff).unwrap();
+        line.push('\n');
+        let mut log = self.log.lock().unwrap();
+        if let Some(file) = log.as_mut() {
+            if let Err(e) = file.write_all(line.as_bytes()).and_then(|()| file.flush()) {
+                log!(
+                    Log::Chain,
+                    "Failed to persist the store at height {}, blocks are no longer persisted: {}",
+                    diff.height,
+                    e
+                );
+                *log = None;
+            }
+        }
+        self.memory.grow();
+        let mut blocks = self.blocks.write().unwrap();
+        blocks.push(block);
+        prune(&self.memory, &mut blocks);
+    }
+
+    fn set_block_data(&self, data: Vec<u8>) {
+        *self.pending_block.lock().unwrap() = Some(data);
+    }
+
+    fn get_block_data(&self, height: u64) -> Option<Vec<u8>> {
+        let blocks = self.blocks.read().unwrap();
+        blocks.get((height as usize).checked_sub(1)?)?.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::test_with_store;
+
+    /// Return a fresh path in the temporary directory.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tendermock-{}-{}.log", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn store() {
+        let path = temp_path("store");
+        test_with_store(FileStore::open(&path, Pruning::KeepAll).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restart() {
+        let path = temp_path("restart");
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        store.set(b"foo".to_vec(), b"bar".to_vec());
+        store.grow();
+        store.set(b"foo".to_vec(), b"baz".to_vec());
+        store.grow();
+        store.delete(b"foo");
+        store.grow();
+        store.set(b"pending".to_vec(), b"lost".to_vec()); // Not commited, lost on restart
+        let root_hash = store.root_hash(0);
+        drop(store);
+
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        assert_eq!(store.height(), 4);
+        assert_eq!(store.get(2, b"foo"), Some(b"bar".to_vec()));
+        assert_eq!(store.get(3, b"foo"), Some(b"baz".to_vec()));
+        assert_eq!(store.get(0, b"foo"), None);
+        assert_eq!(store.get(5, b"pending"), None);
+        assert_eq!(store.root_hash(0), root_hash);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn block_data() {
+        let path = temp_path("block_data");
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        store.set_block_data(b"block 2".to_vec());
+        store.grow();
+        store.grow();
+        store.set_block_data(b"block 4".to_vec());
+        store.grow();
+        assert_eq!(store.get_block_data(4), Some(b"block 4".to_vec()));
+        drop(store);
+
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        assert_eq!(store.get_block_data(1), None);
+        assert_eq!(store.get_block_data(2), Some(b"block 2".to_vec()));
+        assert_eq!(store.get_block_data(3), None);
+        assert_eq!(store.get_block_data(4), Some(b"block 4".to_vec()));
+        assert_eq!(store.get_block_data(5), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_log() {
+        let path = temp_path("truncated");
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        store.set(b"foo".to_vec(), b"bar".to_vec());
+        store.grow();
+        drop(store);
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(b"{\"height\":3,\"oper").unwrap();
+        drop(log);
+
+        // The truncated diff is dropped, and the following ones are appended after it
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        assert_eq!(store.height(), 2);
+        store.grow();
+        drop(store);
+        let store = FileStore::open(&path, Pruning::KeepAll).unwrap();
+        assert_eq!(store.height(), 3);
+        assert_eq!(store.get(0, b"foo"), Some(b"bar".to_vec()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_heights() {
+        let gap = "{\"height\":2,\"operations\":[]}\n{\"height\":4,\"operations\":[]}\n";
+        let out_of_order = "{\"height\":3,\"operations\":[]}\n{\"height\":2,\"operations\":[]}\n";
+        let corrupted = "{\"height\":2,\"oper\n{\"height\":3,\"operations\":[]}\n";
+        let invalid_hex = "{\"height\":2,\"operations\":[{\"set\":[\"666f6f\",\"xyz\"]}]}\n";
+        for (name, log) in &[
+            ("gap", gap),
+            ("order", out_of_order),
+            ("corrupted", corrupted),
+            ("hex", invalid_hex),
+        ] {
+            let path = temp_path(name);
+            std::fs::write(&path, log).unwrap();
+            let err = FileStore::open(&path, Pruning::KeepAll).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
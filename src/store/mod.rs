@@ -0,0 +1,454 @@
+//! # Store
+//!
+//! A storage for tendermock. Two storages are available:
+//!  - the `InMemoryStore`, which ,as its name implies, is not persisted to the hard drive.
+//!  - the `FileStore`, which persists commited blocks to a file so that tendermock can be
+//!    restarted without losing its state.
+//!
+//! The rest of the code base only relies on the `Storage` trait, which may be implemented for new
+//! kinds of storage in the future.
+//!
+//! A storage has two jobs:
+//!  - persist the state of commited blocks.
+//!  - updating the state of the pending block.
+//!
+//! The state of older blocks may be discarded according to a `Pruning` policy, the genesis and
+//! the last commited block are always kept.
+use crate::avl::AvlTree;
+use crate::config;
+use ics23::CommitmentProof;
+use std::sync::RwLock;
+
+mod file;
+mod multistore;
+
+pub use file::FileStore;
+pub use multistore::Multistore;
+
+/// Name of the IBC store within the multistore.
+pub const IBC_STORE: &str = "ibc";
+
+/// A policy describing which historical heights are kept by the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pruning {
+    /// Keep every height.
+    KeepAll,
+    /// Keep only the `N` last commited heights.
+    KeepRecent(u64),
+    /// Keep only the heights that are a multiple of `K`.
+    KeepEvery(u64),
+}
+
+impl Pruning {
+    /// Return true if `height` is kept once `latest` has been commited.
+    pub fn keeps(&self, height: u64, latest: u64) -> bool {
+        if height <= 1 || height >= latest {
+            return true;
+        }
+        match *self {
+            Pruning::KeepAll => true,
+            Pruning::KeepRecent(n) => height + n > latest,
+            Pruning::KeepEvery(k) => k != 0 && height % k == 0,
+        }
+    }
+
+    /// Return the height that gets pruned when `latest` is commited, if any.
+    pub fn pruned_at(&self, latest: u64) -> Option<u64> {
+        let height = match *self {
+            Pruning::KeepAll => return None,
+            // The last commited height is always kept
+            Pruning::KeepRecent(n) => latest.checked_sub(std::cmp::max(n, 1))?,
+            Pruning::KeepEvery(_) => latest.checked_sub(1)?,
+        };
+        if self.keeps(height, latest) {
+            None
+        } else {
+            Some(height)
+        }
+    }
+
+    /// Return the number of consecutive recent heights available when `latest` is the last
+    /// commited height.
+    pub fn history_size(&self, latest: u64) -> u64 {
+        match *self {
+            Pruning::KeepAll => latest,
+            Pruning::KeepRecent(n) => std::cmp::max(1, std::cmp::min(n, latest)),
+            Pruning::KeepEvery(_) => 1,
+        }
+    }
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Pruning::KeepAll
+    }
+}
+
+impl From<config::Pruning> for Pruning {
+    fn from(pruning: config::Pruning) -> Self {
+        match pruning {
+            config::Pruning::KeepAll => Pruning::KeepAll,
+            config::Pruning::KeepRecent(n) => Pruning::KeepRecent(n),
+            config::Pruning::KeepEvery(k) => Pruning::KeepEvery(k),
+        }
+    }
+}
+
+impl From<Pruning> for config::Pruning {
+    fn from(pruning: Pruning) -> Self {
+        match pruning {
+            Pruning::KeepAll => config::Pruning::KeepAll,
+            Pruning::KeepRecent(n) => config::Pruning::KeepRecent(n),
+            Pruning::KeepEvery(k) => config::Pruning::KeepEvery(k),
+        }
+    }
+}
+
+/// A concurrent, on chain storage using interior mutability.
+pub trait Storage: std::fmt::Debug {
+    /// Set a value in the store at the last (pending) height.
+    fn set(&self, path: Vec<u8>, value: Vec<u8>);
+    /// Delete a value from the store at the last (pending) height, previous heights are left
+    /// unchanged.
+    fn delete(&self, path: &[u8]);
+    /// Return None if there is no block matching `height`, or if it has been pruned.
+    fn get(&self, height: u64, path: &[u8]) -> Option<Vec<u8>>;
+    /// Return an ICS23 proof of existence of `path` at `height`, or a proof of non-existence if
+    /// there is no value at `path`.
+    fn get_proof(&self, height: u64, path: &[u8]) -> Option<CommitmentProof>;
+    /// Return the root hash of the store at `height`, or None if the store is empty.
+    fn root_hash(&self, height: u64) -> Option<Vec<u8>>;
+    /// Return the height of the last commited block.
+    fn height(&self) -> u64;
+    /// Return true if the state at `height` has been discarded by the pruning policy.
+    fn is_pruned(&self, height: u64) -> bool;
+    /// Return the pruning policy of the store.
+    fn pruning(&self) -> Pruning;
+    /// Freeze the pending store by adding it to the commited chain and create a new pending, then
+    /// prune older heights according to the pruning policy.
+    fn grow(&self);
+    /// Attach data to the pending block, such as its header, which is not part of the state but
+    /// is needed to restore the block. The data is commited together with the block.
+    fn set_block_data(&self, data: Vec<u8>);
+    /// Return the data attached to the block at `height`, if any. Only persistent stores keep
+    /// block data, as volatile stores are never restored.
+    fn get_block_data(&self, height: u64) -> Option<Vec<u8>>;
+}
+
+/// A type-erased storage, used when the kind of storage is only known at runtime.
+pub type DynStorage = Box<dyn Storage + Send + Sync>;
+
+impl<S: Storage + ?Sized> Storage for Box<S> {
+    fn set(&self, path: Vec<u8>, value: Vec<u8>) {
+        (**self).set(path, value)
+    }
+
+    fn delete(&self, path: &[u8]) {
+        (**self).delete(path)
+    }
+
+    fn get(&self, height: u64, path: &[u8]) -> Option<Vec<u8>> {
+        (**self).get(height, path)
+    }
+
+    fn get_proof(&self, height: u64, path: &[u8]) -> Option<CommitmentProof> {
+        (**self).get_proof(height, path)
+    }
+
+    fn root_hash(&self, height: u64) -> Option<Vec<u8>> {
+        (**self).root_hash(height)
+    }
+
+    fn height(&self) -> u64 {
+        (**self).height()
+    }
+
+    fn is_pruned(&self, height: u64) -> bool {
+        (**self).is_pruned(height)
+    }
+
+    fn pruning(&self) -> Pruning {
+        (**self).pruning()
+    }
+
+    fn grow(&self) {
+        (**self).grow()
+    }
+
+    fn set_block_data(&self, data: Vec<u8>) {
+        (**self).set_block_data(data)
+    }
+
+    fn get_block_data(&self, height: u64) -> Option<Vec<u8>> {
+        (**self).get_block_data(height)
+    }
+}
+
+/// Return the multistore at a given height.
+///
+/// The multistore commits to the root hash of each sub-store and its own root hash is the
+/// application hash. For now tendermock only has a single sub-store, the IBC store.
+pub fn multistore<S: Storage>(store: &S, height: u64) -> Multistore {
+    let mut multistore = Multistore::new();
+    multistore.insert(IBC_STORE, store.root_hash(height).unwrap_or_default());
+    multistore
+}
+
+/// An in-memory store backed by a simple hashmap.
+pub struct InMemoryStore {
+    /// The commited heights, pruned heights are set to None.
+    store: RwLock<Vec<Option<AvlTree<Vec<u8>, Vec<u8>>>>>,
+    pending: RwLock<AvlTree<Vec<u8>, Vec<u8>>>,
+    pruning: Pruning,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        InMemoryStore::with_pruning(Pruning::KeepAll)
+    }
+
+    /// Return an empty store, pruning its history according to `pruning`.
+    pub fn with_pruning(pruning: Pruning) -> Self {
+        let genesis = AvlTree::new();
+        let pending = genesis.clone();
+        InMemoryStore {
+            store: RwLock::new(vec![Some(genesis)]),
+            pending: RwLock::new(pending),
+            pruning,
+        }
+    }
+
+    /// Apply `f` on the tree at a given height.
+    ///
+    /// Three cases:
+    ///  - height = 0 -> last commited block
+    ///  - height - 1 < store.len() -> the block n° (height-1)
+    ///  - height - 1 == store.len() -> the pending block
+    ///
+    /// Returns None if the block has been pruned.
+    fn with_tree<T, F>(&self, height: u64, f: F) -> Option<T>
+    where
+        F: FnOnce(&AvlTree<Vec<u8>, Vec<u8>>) -> Option<T>,
+    {
+        let store = self.store.read().unwrap();
+        if height == 0 {
+            // Access last commited block
+            return f(store.last().unwrap().as_ref().unwrap());
+        }
+        let h = (height - 1) as usize;
+        if h < store.len() {
+            // Access one of the commited blocks
+            f(store.get(h).unwrap().as_ref()?)
+        } else if h == store.len() {
+            // Access the pending blocks
+            drop(store); // Release lock
+            let pending = self.pending.read().unwrap();
+            f(&pending)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Debug for InMemoryStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let store = self.store.read().unwrap();
+        let keys = store.last().unwrap().as_ref().unwrap().get_keys();
+        write!(
+            f,
+            "InMemoryStore {{ height: {}, keys: [{}] }}",
+            store.len(),
+            keys.iter()
+                .map(|k| String::from_utf8_lossy(k).into_owned())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl Storage for InMemoryStore {
+    fn set(&self, path: Vec<u8>, value: Vec<u8>) {
+        let mut store = self.pending.write().unwrap();
+        store.insert(path, value);
+    }
+
+    fn delete(&self, path: &[u8]) {
+        let mut store = self.pending.write().unwrap();
+        store.remove(path);
+    }
+
+    fn get(&self, height: u64, path: &[u8]) -> Option<Vec<u8>> {
+        self.with_tree(height, |tree| tree.get(path).cloned())
+    }
+
+    fn get_proof(&self, height: u64, path: &[u8]) -> Option<CommitmentProof> {
+        self.with_tree(height, |tree| {
+            tree.get_proof(path)
+                .or_else(|| tree.get_non_existence_proof(path))
+        })
+    }
+
+    fn root_hash(&self, height: u64) -> Option<Vec<u8>> {
+        self.with_tree(height, |tree| {
+            tree.root_hash().map(|hash| hash.as_bytes().to_vec())
+        })
+    }
+
+    fn height(&self) -> u64 {
+        self.store.read().unwrap().len() as u64
+    }
+
+    fn is_pruned(&self, height: u64) -> bool {
+        if height == 0 {
+            return false;
+        }
+        let store = self.store.read().unwrap();
+        match store.get((height - 1) as usize) {
+            Some(tree) => tree.is_none(),
+            None => false,
+        }
+    }
+
+    fn pruning(&self) -> Pruning {
+        self.pruning
+    }
+
+    fn grow(&self) {
+        let mut store = self.store.write().unwrap();
+        let pending = self.pending.write().unwrap();
+        // Cheap: the copy shares all its nodes with the pending tree, which are only copied when
+        // modified by the next block.
+        let pending_copy = pending.clone();
+        store.push(Some(pending_copy));
+        if let Some(height) = self.pruning.pruned_at(store.len() as u64) {
+            store[(height - 1) as usize] = None;
+        }
+    }
+
+    fn set_block_data(&self, _data: Vec<u8>) {}
+
+    fn get_block_data(&self, _height: u64) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store() {
+        let store = InMemoryStore::new();
+        test_with_store(store)
+    }
+
+    pub fn test_with_store<T: Storage>(store: T) {
+        let data = b"hello";
+        let path = b"foo/bar";
+        let data = &data[..];
+        let path = &path[..];
+
+        assert_eq!(store.get(0, path), None);
+        store.set(path.to_vec(), data.to_vec()); // Set value on pending block (height 2 here)
+        assert_eq!(store.get(0, path), None);
+        assert_eq!(store.get(2, path), Some(data.to_vec()));
+        store.grow(); // Commit value, will be seen as "last block" (height 0)
+        assert_eq!(store.get(0, path), Some(data.to_vec()));
+        store.delete(path); // Delete value on pending block (height 3 here)
+        assert_eq!(store.get(3, path), None);
+        store.grow();
+        assert_eq!(store.get(0, path), None);
+        assert_eq!(store.get(2, path), Some(data.to_vec())); // Older heights are unchanged
+    }
+
+    #[test]
+    fn pruning() {
+        let store = InMemoryStore::with_pruning(Pruning::KeepRecent(2));
+        let path = b"foo";
+        for i in 0..5u8 {
+            store.set(path.to_vec(), vec![i]);
+            store.grow();
+        }
+        // Heights 1 (genesis), 5 and 6 are kept
+        assert_eq!(store.height(), 6);
+        assert!(!store.is_pruned(1));
+        assert!(store.is_pruned(2));
+        assert!(store.is_pruned(4));
+        assert!(!store.is_pruned(5));
+        assert_eq!(store.get(4, path), None);
+        assert_eq!(store.get(5, path), Some(vec![3]));
+        assert_eq!(store.get(0, path), Some(vec![4]));
+
+        let store = InMemoryStore::with_pruning(Pruning::KeepEvery(2));
+        for i in 0..5u8 {
+            store.set(path.to_vec(), vec![i]);
+            store.grow();
+        }
+        assert!(!store.is_pruned(2));
+        assert!(store.is_pruned(3));
+        assert!(!store.is_pruned(4));
+        assert!(store.is_pruned(5));
+        assert!(!store.is_pruned(6));
+    }
+
+    #[test]
+    fn pruning_policy() {
+        assert!(Pruning::KeepAll.keeps(2, 100));
+        assert!(!Pruning::KeepRecent(10).keeps(90, 100));
+        assert!(Pruning::KeepRecent(10).keeps(91, 100));
+        assert_eq!(Pruning::KeepAll.pruned_at(100), None);
+        assert_eq!(Pruning::KeepRecent(10).pruned_at(100), Some(90));
+        assert_eq!(Pruning::KeepRecent(10).pruned_at(10), None);
+        assert_eq!(Pruning::KeepEvery(10).pruned_at(100), Some(99));
+        assert_eq!(Pruning::KeepEvery(10).pruned_at(101), None);
+        assert_eq!(Pruning::KeepRecent(10).history_size(5), 5);
+        assert_eq!(Pruning::KeepRecent(10).history_size(100), 10);
+    }
+
+    /// Grow a store for 10k blocks, with a small and a large state, and check that the cost per
+    /// block does not depend on the size of the state.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench_growth`.
+    #[test]
+    #[ignore]
+    fn bench_growth() {
+        const BLOCKS: u64 = 10_000;
+        const WRITES_PER_BLOCK: u64 = 10;
+
+        let grow = |state_size: u64| {
+            let store = InMemoryStore::new();
+            for i in 0..state_size {
+                store.set(i.to_be_bytes().to_vec(), vec![0; 32]);
+            }
+            store.grow();
+
+            let start = std::time::Instant::now();
+            for block in 0..BLOCKS {
+                for i in 0..WRITES_PER_BLOCK {
+                    let key = (block * WRITES_PER_BLOCK + i) % state_size;
+                    store.set(key.to_be_bytes().to_vec(), block.to_be_bytes().to_vec());
+                }
+                store.grow();
+            }
+            let per_block = start.elapsed() / BLOCKS as u32;
+            println!(
+                "{} blocks with {} keys: {:?} per block",
+                BLOCKS, state_size, per_block
+            );
+            assert_eq!(store.height(), BLOCKS + 2);
+            assert_eq!(store.get(2, &0u64.to_be_bytes()), Some(vec![0; 32]));
+            per_block
+        };
+
+        let small = grow(100);
+        let large = grow(100_000);
+        // Writes are logarithmic in the size of the state, while copying the whole state on each
+        // block would make the large state about a thousand times slower.
+        assert!(
+            large < small * 10,
+            "The cost per block grows with the state: {:?} vs {:?}",
+            small,
+            large
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! # Multistore
+//!
+//! As in the Cosmos SDK, the multistore is a Tendermint simple merkle tree (see
+//! `tendermint::merkle`) whose leaves are the sub-stores, as (name, root hash) pairs ordered by
+//! name. Its root hash is the application hash, and the proofs of the sub-stores root hashes can
+//! be verified with the ICS23 `tendermint_spec`.
+use ics23::commitment_proof::Proof;
+use ics23::{CommitmentProof, ExistenceProof, HashOp, InnerOp, LeafOp, LengthOp};
+use prost::encoding::encode_varint;
+use sha2::{Digest, Sha256};
+use tendermint::merkle::simple_hash_from_byte_vectors;
+
+/// Prefix of the inner nodes of a simple merkle tree, leaves are prefixed with 0.
+const INNER_PREFIX: u8 = 1;
+
+/// The root hashes of the sub-stores at a given height.
+pub struct Multistore {
+    /// The sub-stores, ordered by name.
+    stores: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl Multistore {
+    pub fn new() -> Self {
+        Multistore { stores: Vec::new() }
+    }
+
+    /// Add a sub-store, or update its root hash.
+    pub fn insert(&mut self, name: &'static str, root_hash: Vec<u8>) {
+        match self.stores.binary_search_by(|(n, _)| n.cmp(&name)) {
+            Ok(idx) => self.stores[idx].1 = root_hash,
+            Err(idx) => self.stores.insert(idx, (name, root_hash)),
+        }
+    }
+
+    /// Return the root hash of the multistore.
+    pub fn root_hash(&self) -> Vec<u8> {
+        simple_hash_from_byte_vectors(self.leaves()).to_vec()
+    }
+
+    /// Return an existence proof of the root hash of the sub-store `name`.
+    pub fn get_proof(&self, name: &str) -> Option<CommitmentProof> {
+        let idx = self.stores.iter().position(|(n, _)| *n == name)?;
+        let (name, root_hash) = &self.stores[idx];
+        let mut path = Vec::new();
+        inner_path(&self.leaves(), idx, &mut path);
+        Some(CommitmentProof {
+            proof: Some(Proof::Exist(ExistenceProof {
+                key: name.as_bytes().to_vec(),
+                value: root_hash.clone(),
+                leaf: Some(LeafOp {
+                    hash: HashOp::Sha256.into(),
+                    prehash_key: HashOp::NoHash.into(),
+                    prehash_value: HashOp::Sha256.into(),
+                    length: LengthOp::VarProto.into(),
+                    prefix: vec![0],
+                }),
+                path,
+            })),
+        })
+    }
+
+    /// Return the leaves of the merkle tree, before hashing.
+    fn leaves(&self) -> Vec<Vec<u8>> {
+        self.stores
+            .iter()
+            .map(|(name, root_hash)| leaf_bytes(name.as_bytes(), root_hash))
+            .collect()
+    }
+}
+
+/// Encode a (key, value) pair as a leaf: the length-prefixed key followed by the length-prefixed
+/// hash of the value.
+fn leaf_bytes(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let value_hash = Sha256::digest(value);
+    let mut bytes = Vec::new();
+    encode_varint(key.len() as u64, &mut bytes);
+    bytes.extend_from_slice(key);
+    encode_varint(value_hash.len() as u64, &mut bytes);
+    bytes.extend_from_slice(&value_hash);
+    bytes
+}
+
+/// Push the inner operations leading from the leaf at `idx` to the root of the tree built from
+/// `leaves`, the tree is split as in Tendermint.
+fn inner_path(leaves: &[Vec<u8>], idx: usize, path: &mut Vec<InnerOp>) {
+    if leaves.len() <= 1 {
+        return;
+    }
+    let split = split_point(leaves.len());
+    let (left, right) = leaves.split_at(split);
+    let (prefix, suffix) = if idx < split {
+        inner_path(left, idx, path);
+        let right_hash = simple_hash_from_byte_vectors(right.to_vec());
+        (vec![INNER_PREFIX], right_hash.to_vec())
+    } else {
+        inner_path(right, idx - split, path);
+        let mut prefix = vec![INNER_PREFIX];
+        prefix.extend_from_slice(&simple_hash_from_byte_vectors(left.to_vec()));
+        (prefix, vec![])
+    };
+    path.push(InnerOp {
+        hash: HashOp::Sha256.into(),
+        prefix,
+        suffix,
+    });
+}
+
+/// Return the largest power of two strictly smaller than `n`.
+fn split_point(n: usize) -> usize {
+    let mut split = 1;
+    while split * 2 < n {
+        split *= 2;
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ics23::{tendermint_spec, verify_membership};
+
+    #[test]
+    fn proofs() {
+        let names = ["acc", "bank", "ibc", "staking", "upgrade"];
+        let mut multistore = Multistore::new();
+        for name in names.iter().rev() {
+            multistore.insert(*name, Sha256::digest(name.as_bytes()).to_vec());
+        }
+        let root = multistore.root_hash();
+        for name in names.iter() {
+            let proof = multistore.get_proof(name).unwrap();
+            let value = Sha256::digest(name.as_bytes()).to_vec();
+            assert!(verify_membership(
+                &proof,
+                &tendermint_spec(),
+                &root,
+                name.as_bytes(),
+                &value
+            ));
+        }
+        assert!(multistore.get_proof("gov").is_none());
+    }
+}
@@ -3,13 +3,17 @@
 mod tests {
     use crate::config;
     use crate::node::*;
+    use crate::packets::{PacketKeeper, PacketReader};
     use ibc::ics02_client::client_def::{AnyClientState, AnyConsensusState};
     use ibc::ics02_client::client_type::ClientType;
     use ibc::ics02_client::context::{ClientKeeper, ClientReader};
+    use ibc::ics03_connection::context::ConnectionKeeper;
+    use ibc::ics04_channel::channel::{ChannelEnd, Counterparty, Order, State};
+    use ibc::ics04_channel::context::{ChannelKeeper, ChannelReader};
     use ibc::ics07_tendermint::client_state::ClientState;
     use ibc::ics07_tendermint::consensus_state::ConsensusState;
     use ibc::ics23_commitment::commitment::CommitmentRoot;
-    use ibc::ics24_host::identifier::ClientId;
+    use ibc::ics24_host::identifier::{ChannelId, ClientId, ConnectionId, PortId};
     use ibc::Height;
     use std::convert::TryInto;
     use std::str::FromStr;
@@ -43,6 +47,248 @@ mod tests {
         assert_eq!(consensus_state, retrieved_consensus);
     }
 
+    #[test]
+    /// Test that no block is commited while a transaction is being delivered.
+    fn lock_commits() {
+        use std::sync::{mpsc, Arc, Barrier};
+        use std::time::Duration;
+        use tendermint::abci::{Code, Transaction};
+        use tendermint_rpc::endpoint::broadcast::tx_commit::TxResult;
+
+        let node = Node::new(&config::Config::default()).shared();
+        let commits = node.lock_commits();
+
+        // A concurrent growth, started once the commits are locked
+        let started = Arc::new(Barrier::new(2));
+        let (grown, concurrent_growth) = mpsc::channel();
+        let concurrent_node = node.clone();
+        let concurrent_started = started.clone();
+        let handle = std::thread::spawn(move || {
+            concurrent_started.wait();
+            concurrent_node.grow();
+            grown.send(()).unwrap();
+        });
+        started.wait();
+
+        // Deliver and commit a transaction, as `broadcast_tx_commit` does
+        let result = TxResult {
+            code: Code::Ok,
+            data: None,
+            log: "Success".into(),
+            codespace: Default::default(),
+            gas_used: 10.into(),
+            gas_wanted: 10.into(),
+            info: Default::default(),
+            events: vec![],
+        };
+        let tx = node
+            .read()
+            .get_chain()
+            .deliver_tx(Transaction::new(b"tx".to_vec()), result);
+        // Without the lock the concurrent growth would complete here
+        assert!(concurrent_growth
+            .recv_timeout(Duration::from_millis(200))
+            .is_err());
+        node.write().get_chain().grow();
+        assert_eq!(tx.height, 2);
+        assert_eq!(node.read().get_chain().get_height().revision_height, 2);
+        assert_eq!(node.read().get_chain().get_tx(&tx.hash).unwrap().height, 2);
+
+        // The concurrent growth lands once the commits are released
+        drop(commits);
+        concurrent_growth.recv().unwrap();
+        handle.join().unwrap();
+        assert_eq!(node.read().get_chain().get_height().revision_height, 3);
+    }
+
+    #[test]
+    /// Test that the configured validators sign the blocks.
+    fn validators() {
+        let mut config = config::Config::default();
+        config.validators = vec![
+            config::Validator {
+                id: String::from("alice"),
+                voting_power: 10,
+            },
+            config::Validator {
+                id: String::from("bob"),
+                voting_power: 20,
+            },
+        ];
+        let node = Node::new(&config);
+        node.grow();
+        let validator = node.get_validator_info().unwrap();
+        let block = node.get_chain().get_block(0).unwrap();
+        let validators = block.validators.validators();
+        assert_eq!(validators.len(), 2);
+        assert!(validators.iter().any(|v| v.address == validator.address));
+        assert_eq!(
+            block.signed_header.header.chain_id.as_str(),
+            config.chain_id
+        );
+    }
+
+    #[test]
+    /// Test validator set changes, from the config and at runtime.
+    fn validator_changes() {
+        let mut config = config::Config::default();
+        config.validator_changes = vec![config::ValidatorChange {
+            height: 3,
+            id: String::from("bob"),
+            voting_power: 20,
+        }];
+        let node = Node::new(&config).shared();
+        let own_power = node.read().get_validator_info().unwrap().voting_power;
+        assert!(node.set_validator(4, &config.validators[0].id, 0));
+        assert!(!node.set_validator(1, "bob", 10));
+        let validators = |node: &SharedNode<_>| {
+            node.read()
+                .get_chain()
+                .get_block(0)
+                .unwrap()
+                .validators
+                .validators()
+                .len()
+        };
+        node.grow();
+        assert_eq!(validators(&node), 1);
+        node.grow();
+        assert_eq!(validators(&node), 2);
+        node.grow();
+        assert_eq!(validators(&node), 1);
+        // This node's validator has been removed
+        let info = node.read().get_validator_info().unwrap();
+        assert_ne!(info.voting_power, own_power);
+    }
+
+    #[test]
+    /// Test manual block production.
+    fn block_production() {
+        let node = Node::new(&config::Config::default()).shared();
+        assert!(!node.is_paused());
+        node.pause();
+        assert!(node.is_paused());
+        node.grow_n(3);
+        assert_eq!(node.read().get_chain().get_height().revision_height, 4);
+        node.resume();
+        assert!(!node.is_paused());
+    }
+
+    #[test]
+    /// Test storage and retrieval of channel ends and connection channels.
+    fn channel() {
+        let node = Node::new(&config::Config::default());
+        let mut node = node.shared();
+        let connection_id = ConnectionId::from_str("connection-0").unwrap();
+        let port_id = PortId::from_str("transfer").unwrap();
+        let channel_a = (port_id.clone(), ChannelId::from_str("channel-0").unwrap());
+        let channel_b = (port_id.clone(), ChannelId::from_str("channel-1").unwrap());
+        let channel_end = dummy_channel_end(&connection_id);
+
+        node.store_channel(&channel_a, &channel_end).unwrap();
+        node.store_connection_channels(&connection_id, &channel_a)
+            .unwrap();
+        node.store_connection_channels(&connection_id, &channel_b)
+            .unwrap();
+        node.store_next_sequence_send(&channel_a, 1).unwrap();
+        node.grow();
+        let retrieved_channel = node.channel_end(&channel_a).unwrap();
+        assert_eq!(channel_end, retrieved_channel);
+        let channels = node.connection_channels(&connection_id).unwrap();
+        assert_eq!(channels, vec![channel_a, channel_b]);
+    }
+
+    #[test]
+    /// Test that identifier counters are persisted and increase.
+    fn counters() {
+        let node = Node::new(&config::Config::default());
+        let mut node = node.shared();
+
+        assert_eq!(node.client_counter(), 0);
+        node.increase_client_counter();
+        node.increase_client_counter();
+        assert_eq!(node.client_counter(), 2);
+        let connection_id = node.next_connection_id();
+        assert_eq!(connection_id.as_str(), "connection-0");
+        node.grow();
+        let connection_id = node.next_connection_id();
+        assert_eq!(connection_id.as_str(), "connection-1");
+        let channel_id = node.next_channel_id();
+        assert_eq!(channel_id.as_str(), "channel-0");
+        node.grow();
+        let counter = node
+            .read()
+            .get_store()
+            .get(0, b"nextClientSequence")
+            .unwrap();
+        assert_eq!(counter, 2u64.to_be_bytes().to_vec());
+    }
+
+    #[test]
+    /// Test storage and retrieval of packet commitments, receipts and acknowledgements.
+    fn packets() {
+        let node = Node::new(&config::Config::default());
+        let mut node = node.shared();
+        let port_channel_id = (
+            PortId::from_str("transfer").unwrap(),
+            ChannelId::from_str("channel-0").unwrap(),
+        );
+        let commitment = vec![1, 2, 3];
+        let ack = vec![4, 5, 6];
+
+        node.store_packet_commitment(&port_channel_id, 1, commitment.clone())
+            .unwrap();
+        node.store_packet_receipt(&port_channel_id, 1).unwrap();
+        node.store_packet_acknowledgement(&port_channel_id, 1, ack.clone())
+            .unwrap();
+        assert_eq!(node.packet_commitment(&port_channel_id, 1), None); // Not yet commited
+        node.grow();
+        assert_eq!(
+            node.packet_commitment(&port_channel_id, 1),
+            Some(commitment)
+        );
+        assert_eq!(node.packet_acknowledgement(&port_channel_id, 1), Some(ack));
+        assert!(node.packet_receipt(&port_channel_id, 1));
+        assert!(!node.packet_receipt(&port_channel_id, 2));
+        let path = b"commitments/ports/transfer/channels/channel-0/sequences/1";
+        assert!(node.read().get_store().get(0, path).is_some());
+        node.delete_packet_commitment(&port_channel_id, 1).unwrap();
+        node.delete_packet_receipt(&port_channel_id, 1).unwrap();
+        node.grow();
+        assert_eq!(node.packet_commitment(&port_channel_id, 1), None);
+        assert!(!node.packet_receipt(&port_channel_id, 1));
+    }
+
+    #[test]
+    /// Test that connections are accumulated in the client's connection list.
+    fn client_connections() {
+        let node = Node::new(&config::Config::default());
+        let mut node = node.shared();
+        let client_id = ClientId::from_str("UncleScrooge").unwrap();
+        let connection_a = ConnectionId::from_str("connection-0").unwrap();
+        let connection_b = ConnectionId::from_str("connection-1").unwrap();
+
+        node.store_connection_to_client(&connection_a, &client_id)
+            .unwrap();
+        node.grow();
+        node.store_connection_to_client(&connection_b, &client_id)
+            .unwrap();
+        node.grow();
+        let connections = node.client_connections(&client_id).unwrap();
+        assert_eq!(connections, vec![connection_a, connection_b]);
+    }
+
+    fn dummy_channel_end(connection_id: &ConnectionId) -> ChannelEnd {
+        let counterparty = Counterparty::new(PortId::from_str("transfer").unwrap(), None);
+        ChannelEnd::new(
+            State::Init,
+            Order::Unordered,
+            counterparty,
+            vec![connection_id.clone()],
+            String::from("ics20-1"),
+        )
+    }
+
     fn dummy_consensus_state() -> AnyConsensusState {
         let root = CommitmentRoot::from_bytes(b"root");
         let tm_consensus_state = ConsensusState {
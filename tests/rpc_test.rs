@@ -1,16 +1,24 @@
 //! Integration tests for tendermock JsonRPC and gRPC server.
+use ibc::ics02_client::client_def::{
+    AnyClient, AnyClientState, AnyConsensusState, AnyHeader, ClientDef,
+};
+use ibc::ics02_client::client_type::ClientType;
+use ibc::ics02_client::context::{ClientKeeper, ClientReader};
+use ibc::ics07_tendermint::header::Header;
+use ibc::ics23_commitment::commitment::CommitmentRoot;
+use ibc::ics24_host::identifier::ClientId;
 use ibc_proto::cosmos::staking::v1beta1::query_client::QueryClient;
 use ibc_proto::cosmos::staking::v1beta1::QueryParamsRequest;
+use std::net::SocketAddr;
 use std::process::{Command, Stdio};
-use tendermock::Tendermock;
+use std::str::FromStr;
+use tendermint_light_client::components::verifier::{ProdVerifier, Verdict, Verifier};
+use tendermint_light_client::light_client::Options;
+use tendermint_light_client::types::LightBlock;
+use tendermock::{Network, Tendermock, TendermockHandle};
 use tokio;
 use tonic;
 
-const JSON_RPC_ADDR: &str = "127.0.0.1:26657";
-const JSON_RPC_ADDR_2: &str = "127.0.0.1:26658";
-const GRPC_ADDR: &str = "127.0.0.1:50051";
-const GRPC_ADDR_2: &str = "127.0.0.1:50052";
-const GRPC_URL: &str = "http://127.0.0.1:50051";
 const JRPC_QUERIES: &[&str] = &[
     "abci_info.json",
     "abci_query.json",
@@ -19,32 +27,174 @@ const JRPC_QUERIES: &[&str] = &[
     "genesis.json",
     "status.json",
     "validators.json",
+    "tx_search.json",
+    "tendermock_fork.json",
+    "tendermock_grow.json",
+    "tendermock_grow_n.json",
+    "tendermock_pause.json",
+    "tendermock_resume.json",
+    "tendermock_set_time.json",
+    "tendermock_advance_time.json",
 ];
 
-/// Spwan a server in another thread.
-fn start_server() {
-    let mut node = Tendermock::new();
-    node.add_interface(JSON_RPC_ADDR.parse().unwrap(), GRPC_ADDR.parse().unwrap())
-        .add_interface(
-            JSON_RPC_ADDR_2.parse().unwrap(),
-            GRPC_ADDR_2.parse().unwrap(),
-        );
-    std::thread::spawn(move || node.start());
-    std::thread::sleep(std::time::Duration::new(2, 0));
+/// Spwan a server in another thread, listening on ephemeral ports.
+fn start_server() -> TendermockHandle {
+    Tendermock::new()
+        .add_ephemeral_interface()
+        .add_ephemeral_interface()
+        .spawn()
 }
 
 #[tokio::test]
 async fn rpc() {
-    start_server();
-    test_grpc().await;
+    let handle = start_server();
+    test_grpc(handle.grpc_addr()).await;
+    let jrpc_addr = handle.jrpc_addr().to_string();
     for query in JRPC_QUERIES {
-        test_json_rpg(query, JSON_RPC_ADDR);
+        test_json_rpg(query, &jrpc_addr);
     }
-    test_json_rpg(JRPC_QUERIES[0], JSON_RPC_ADDR_2)
+    let jrpc_addr_2 = handle.interfaces()[1].0.to_string();
+    test_json_rpg(JRPC_QUERIES[0], &jrpc_addr_2);
+    handle.shutdown();
+}
+
+#[tokio::test]
+async fn parallel_nodes() {
+    let handle_a = start_server();
+    let handle_b = start_server();
+    assert_ne!(handle_a.jrpc_addr(), handle_b.jrpc_addr());
+    assert_ne!(handle_a.grpc_addr(), handle_b.grpc_addr());
+    test_grpc(handle_a.grpc_addr()).await;
+    test_grpc(handle_b.grpc_addr()).await;
+    test_json_rpg(JRPC_QUERIES[0], &handle_a.jrpc_addr().to_string());
+    test_json_rpg(JRPC_QUERIES[0], &handle_b.jrpc_addr().to_string());
+    handle_a.shutdown();
+    handle_b.shutdown();
+}
+
+#[tokio::test]
+async fn network() {
+    let handle = Network::new()
+        .add_chain(
+            Tendermock::new()
+                .chain_id("chain-a")
+                .add_ephemeral_interface(),
+        )
+        .add_chain(
+            Tendermock::new()
+                .chain_id("chain-b")
+                .add_ephemeral_interface(),
+        )
+        .spawn();
+    for (host, counterparty) in &[("chain-a", "chain-b"), ("chain-b", "chain-a")] {
+        let client_id = handle.client_id(host, counterparty).unwrap();
+        let client_id = ClientId::from_str(client_id).unwrap();
+        let node = handle.chain(host).unwrap().node();
+        let client_state = node.client_state(&client_id).unwrap();
+        assert_eq!(client_state.client_type(), ClientType::Tendermint);
+        assert!(node
+            .consensus_state(&client_id, client_state.latest_height())
+            .is_some());
+        let jrpc_addr = handle.chain(host).unwrap().jrpc_addr().to_string();
+        test_json_rpg(JRPC_QUERIES[0], &jrpc_addr);
+    }
+    handle.shutdown();
+}
+
+/// Update a client created at genesis with a new header of its counterparty, as a relayer would.
+#[tokio::test]
+async fn network_client_update() {
+    let handle = Network::new()
+        .add_chain(
+            Tendermock::new()
+                .chain_id("chain-a")
+                .add_ephemeral_interface(),
+        )
+        .add_chain(
+            Tendermock::new()
+                .chain_id("chain-b")
+                .add_ephemeral_interface(),
+        )
+        .spawn();
+    let client_id = handle.client_id("chain-a", "chain-b").unwrap();
+    let client_id = ClientId::from_str(client_id).unwrap();
+    let mut host = handle.chain("chain-a").unwrap().node().clone();
+    let counterparty = handle.chain("chain-b").unwrap().node();
+    let client_state = host.client_state(&client_id).unwrap();
+    let trusted_height = client_state.latest_height();
+    let (trusting_period, clock_drift) = match &client_state {
+        AnyClientState::Tendermint(state) => (state.trusting_period, state.max_clock_drift),
+        #[allow(unreachable_patterns)]
+        _ => panic!("Expected a Tendermint client"),
+    };
+    let consensus_state = match host.consensus_state(&client_id, trusted_height).unwrap() {
+        AnyConsensusState::Tendermint(state) => state,
+        #[allow(unreachable_patterns)]
+        _ => panic!("Expected a Tendermint consensus state"),
+    };
+    counterparty.grow();
+    let (trusted, target) = {
+        let node = counterparty.read();
+        let chain = node.get_chain();
+        let trusted = chain.get_block(trusted_height.revision_height).unwrap();
+        (trusted, chain.get_block(0).unwrap())
+    };
+
+    // The client trusts a committed header, with a real time and application hash
+    let trusted_header = &trusted.signed_header.header;
+    assert!(!trusted_header.app_hash.as_ref().is_empty());
+    assert_eq!(
+        consensus_state.root,
+        CommitmentRoot::from_bytes(trusted_header.app_hash.as_ref())
+    );
+    assert_eq!(consensus_state.timestamp, trusted_header.time);
+    let header = AnyHeader::Tendermint(Header {
+        signed_header: target.signed_header.clone(),
+        validator_set: target.validators.clone(),
+        trusted_height,
+        trusted_validator_set: trusted.next_validators.clone(),
+    });
+    let target_height = target.signed_header.header.height.value();
+
+    // The new header is verified within the trusting period of the client
+    let to_light_block = |block: tendermint_testgen::light_block::TMLightBlock| {
+        LightBlock::new(
+            block.signed_header,
+            block.validators,
+            block.next_validators,
+            block.provider,
+        )
+    };
+    let options = Options {
+        trust_threshold: Default::default(),
+        trusting_period,
+        clock_drift,
+    };
+    let verdict = ProdVerifier::default().verify(
+        &to_light_block(target),
+        &to_light_block(trusted),
+        &options,
+        tendermint::Time::now(),
+    );
+    assert!(matches!(verdict, Verdict::Success));
+
+    // Then the client is updated
+    let (client_state, consensus_state) = AnyClient::from_client_type(ClientType::Tendermint)
+        .check_header_and_update_state(client_state, header)
+        .unwrap();
+    let height = client_state.latest_height();
+    assert_eq!(height.revision_height, target_height);
+    host.store_client_state(client_id.clone(), client_state)
+        .unwrap();
+    host.store_consensus_state(client_id.clone(), height, consensus_state)
+        .unwrap();
+    assert!(host.consensus_state(&client_id, height).is_some());
+    handle.shutdown();
 }
 
-async fn test_grpc() {
-    let mut client = QueryClient::connect(GRPC_URL).await.unwrap();
+async fn test_grpc(addr: SocketAddr) {
+    let url = format!("http://{}", addr);
+    let mut client = QueryClient::connect(url).await.unwrap();
     let request = tonic::Request::new(QueryParamsRequest {});
     client
         .params(request)